@@ -1,13 +1,26 @@
 // some default named functions are tb/links, tb/togglebot
 type named_function = `@${string}/${string}`;
 type raw_string = string | `$${string}`;
-type arg_type = "url" | "string" | `string${number}` | `string...`;
+type arg_type =
+  | "url"
+  | "string"
+  | `string${number}`
+  | "int"
+  | `int(${number}..${"" | "="}${number})`
+  | `choice(${string})`
+  | "user"
+  | "duration";
+// optional, with a default value or taking all remaining words (only for the last argument)
+type arity = "" | "?" | `=${string}` | "...";
 type parse_mode = "?" | "!";
+// optional name shown in the usage of a command, like `name:` for `!crate <name>`
+type arg_name = "" | `${string}:`;
 type argument =
-  | `${arg_type}`
-  | `${arg_type}${parse_mode}${string}`
-  // Syntax: type ?|! parser_argument <!> error_message
-  | `${arg_type}${parse_mode}${string}<!>${string}`;
+  | `${arg_name}${arg_type}${arity}`
+  | `${arg_name}${arg_type}${arity}${parse_mode}${string}`
+  // Syntax: name: type arity ?|! parser_argument <!> error_message
+  | `${arg_name}${arg_type}${arity}${parse_mode}${string}<!>${string}`
+  | `${arg_name}${arg_type}${arity}<!>${string}`;
 
 // One of the platforms specified in config.platforms
 type platform = string;
@@ -0,0 +1,295 @@
+//! Announcements that are posted to the configured platforms shortly before a scheduled stream
+//! starts.
+
+use std::time::Duration as StdDuration;
+
+use chrono::{prelude::*, Duration};
+use chrono_tz::{Tz, CET};
+use log::{info, warn};
+use tokio::{select, time};
+
+use crate::{
+    handler::AsyncState,
+    settings::{Announcements, State},
+    Outboxes, Outgoing, Shutdown,
+};
+
+/// How many days ahead the schedule is searched for the next stream.
+const LOOKAHEAD_DAYS: i64 = 14;
+/// How often the schedule is checked for due announcements.
+const POLL_INTERVAL: StdDuration = StdDuration::from_secs(30);
+
+/// Source of the current time, so the scheduling logic can be driven by a fake clock in tests.
+pub trait Clock: Send + Sync + 'static {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// The real system clock.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A single upcoming stream, as derived from the schedule.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Stream {
+    pub start: DateTime<Tz>,
+    pub title: Option<String>,
+}
+
+/// Find the next stream that starts at or after `now`, respecting off days and all schedule
+/// exceptions.
+#[must_use]
+pub fn next_stream(state: &State, now: DateTime<Utc>) -> Option<Stream> {
+    let now = now.with_timezone(&CET);
+    let today = now.date().naive_local();
+
+    (0..LOOKAHEAD_DAYS)
+        .map(|offset| today + Duration::days(offset))
+        .flat_map(|date| streams_on(state, date))
+        .find(|stream| stream.start >= now)
+}
+
+/// Find the latest stream that started at or before `now`, respecting off days and all schedule
+/// exceptions.
+#[must_use]
+pub fn last_stream(state: &State, now: DateTime<Utc>) -> Option<Stream> {
+    let now = now.with_timezone(&CET);
+    let today = now.date().naive_local();
+
+    (0..LOOKAHEAD_DAYS)
+        .map(|offset| today - Duration::days(offset))
+        .flat_map(|date| streams_on(state, date).into_iter().rev())
+        .find(|stream| stream.start <= now)
+}
+
+/// All streams on a single day, sorted by their start time. Extra streams are always included,
+/// while the regular stream is dropped for off days, skipped days and vacations.
+fn streams_on(state: &State, date: NaiveDate) -> Vec<Stream> {
+    let exceptions = &state.schedule_exceptions;
+    let regular = (!state.off_days.contains(&date.weekday()) && !exceptions.is_off(date))
+        .then_some((state.schedule.start.0, None));
+    let extra = exceptions
+        .extras
+        .get(&date)
+        .map(|extra| (extra.start, extra.title.clone()));
+
+    let mut streams = regular
+        .into_iter()
+        .chain(extra)
+        .filter_map(|(time, title)| {
+            CET.from_local_datetime(&date.and_time(time))
+                .earliest()
+                .map(|start| Stream { start, title })
+        })
+        .collect::<Vec<_>>();
+
+    streams.sort_by_key(|stream| stream.start);
+    streams
+}
+
+/// Decides when an announcement is due and remembers which stream was announced last, so each
+/// stream is only announced once.
+pub struct Announcer {
+    config: Announcements,
+    last: Option<DateTime<Tz>>,
+}
+
+impl Announcer {
+    #[must_use]
+    pub const fn new(config: Announcements) -> Self {
+        Self { config, last: None }
+    }
+
+    /// Check whether the announcement for the next stream is due at the given time and return
+    /// the message to post if so.
+    pub fn poll(&mut self, state: &State, now: DateTime<Utc>) -> Option<String> {
+        let stream = next_stream(state, now)?;
+        if self.last == Some(stream.start) {
+            return None;
+        }
+
+        let until = stream.start.signed_duration_since(now);
+        if until > Duration::minutes(self.config.minutes_before.into()) {
+            return None;
+        }
+
+        self.last = Some(stream.start);
+        Some(self.format(&stream, until))
+    }
+
+    fn format(&self, stream: &Stream, until: Duration) -> String {
+        let title = stream
+            .title
+            .as_deref()
+            .or_else(|| {
+                self.config
+                    .titles
+                    .get(&stream.start.weekday())
+                    .map(String::as_str)
+            })
+            .or(self.config.title.as_deref())
+            .unwrap_or_default();
+        let minutes = (until.num_seconds() + 59) / 60;
+
+        self.config
+            .message
+            .replace("{title}", title)
+            .replace("{start}", &stream.start.format("%I:%M%P").to_string())
+            .replace("{minutes}", &minutes.to_string())
+    }
+}
+
+/// Periodically check the schedule and post announcements to all configured targets until a
+/// shutdown is requested.
+pub async fn run(
+    config: Announcements,
+    state: AsyncState,
+    outboxes: Outboxes,
+    clock: impl Clock,
+    mut shutdown: Shutdown,
+) {
+    let targets = config.targets.clone();
+    let mut announcer = Announcer::new(config);
+    let mut interval = time::interval(POLL_INTERVAL);
+
+    loop {
+        select! {
+            _ = shutdown.recv() => break,
+            _ = interval.tick() => {}
+        }
+
+        let content = announcer.poll(&*state.read().await, clock.now());
+        if let Some(content) = content {
+            info!("posting stream announcement");
+
+            for target in &targets {
+                if let Some(outbox) = outboxes.get(&target.platform) {
+                    let message = Outgoing {
+                        channel: target.channel,
+                        content: content.clone(),
+                    };
+                    if outbox.send(message).await.is_err() {
+                        warn!("platform `{}` is not running anymore", target.platform);
+                    }
+                } else {
+                    warn!("unknown announcement platform `{}`", target.platform);
+                }
+            }
+        }
+    }
+
+    info!("announcements shutting down");
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::settings::ExtraStream;
+
+    fn config() -> Announcements {
+        Announcements {
+            minutes_before: 15,
+            message: "{title} starts at {start}, in {minutes} minutes!".to_owned(),
+            title: Some("Coding".to_owned()),
+            titles: vec![(Weekday::Tue, "Game dev".to_owned())]
+                .into_iter()
+                .collect(),
+            targets: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn next_regular_stream() {
+        let state = State::default();
+        // Sunday is an off day by default, so the next stream is on Monday morning.
+        let now = Utc.ymd(2026, 12, 20).and_hms(12, 0, 0);
+
+        assert_eq!(
+            Some(Stream {
+                start: CET.ymd(2026, 12, 21).and_hms(7, 0, 0),
+                title: None,
+            }),
+            next_stream(&state, now)
+        );
+    }
+
+    #[test]
+    fn next_stream_with_exceptions() {
+        let mut state = State::default();
+        state
+            .schedule_exceptions
+            .skips
+            .insert(NaiveDate::from_ymd(2026, 12, 21));
+        state.schedule_exceptions.extras.insert(
+            NaiveDate::from_ymd(2026, 12, 20),
+            ExtraStream {
+                start: NaiveTime::from_hms(14, 0, 0),
+                finish: NaiveTime::from_hms(18, 0, 0),
+                title: Some("Holiday stream".to_owned()),
+            },
+        );
+
+        let now = Utc.ymd(2026, 12, 20).and_hms(10, 0, 0);
+        assert_eq!(
+            Some(Stream {
+                start: CET.ymd(2026, 12, 20).and_hms(14, 0, 0),
+                title: Some("Holiday stream".to_owned()),
+            }),
+            next_stream(&state, now)
+        );
+
+        let now = Utc.ymd(2026, 12, 20).and_hms(14, 0, 0);
+        assert_eq!(
+            Some(Stream {
+                start: CET.ymd(2026, 12, 22).and_hms(7, 0, 0),
+                title: None,
+            }),
+            next_stream(&state, now)
+        );
+    }
+
+    #[test]
+    fn last_regular_stream() {
+        let state = State::default();
+        // Monday 06:00 CET, before the stream, so the last one was on Friday.
+        let now = Utc.ymd(2026, 12, 21).and_hms(5, 0, 0);
+
+        assert_eq!(
+            Some(CET.ymd(2026, 12, 18).and_hms(7, 0, 0)),
+            last_stream(&state, now).map(|stream| stream.start)
+        );
+    }
+
+    #[test]
+    fn announce_once() {
+        let state = State::default();
+        let mut announcer = Announcer::new(config());
+
+        // Monday 06:40 CET, still too early.
+        assert_eq!(
+            None,
+            announcer.poll(&state, Utc.ymd(2026, 12, 21).and_hms(5, 40, 0))
+        );
+        // Monday 06:50 CET, within the lead time.
+        assert_eq!(
+            Some("Coding starts at 07:00am, in 10 minutes!".to_owned()),
+            announcer.poll(&state, Utc.ymd(2026, 12, 21).and_hms(5, 50, 0))
+        );
+        // Already announced.
+        assert_eq!(
+            None,
+            announcer.poll(&state, Utc.ymd(2026, 12, 21).and_hms(5, 52, 0))
+        );
+        // Tuesday 06:46 CET, with the weekday specific title.
+        assert_eq!(
+            Some("Game dev starts at 07:00am, in 14 minutes!".to_owned()),
+            announcer.poll(&state, Utc.ymd(2026, 12, 22).and_hms(5, 46, 0))
+        );
+    }
+}
@@ -0,0 +1,425 @@
+//! Export and import of the whole state as versioned bundles, and periodic backups of it.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration as StdDuration,
+};
+
+use anyhow::{bail, Context, Result};
+use chrono::prelude::*;
+use log::{error, info};
+use serde::Serialize;
+use serde_json::Value;
+use tokio::{select, time};
+
+use crate::{
+    announce::Clock,
+    handler::AsyncState,
+    settings::{self, Backups, State},
+    Shutdown,
+};
+
+/// File name prefix of periodic backups, which are the only files that are rotated.
+const BACKUP_PREFIX: &str = "state-";
+
+/// File format of an exported bundle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Toml,
+}
+
+impl Format {
+    /// Guess the format from the file extension, falling back to JSON.
+    #[must_use]
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Self::Toml,
+            _ => Self::Json,
+        }
+    }
+
+    #[must_use]
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Toml => "toml",
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_lowercase().as_ref() {
+            "json" => Self::Json,
+            "toml" => Self::Toml,
+            _ => bail!("unknown format `{}`, must be `json` or `toml`", s),
+        })
+    }
+}
+
+/// The exported state, together with the time of the export. The bundle's version is the one of
+/// the contained state, so older bundles are migrated when importing them.
+#[derive(Serialize)]
+struct Bundle<'a> {
+    exported: DateTime<Utc>,
+    state: &'a State,
+}
+
+/// Serialize the whole state into a bundle of the given format.
+pub fn export(state: &State, format: Format, now: DateTime<Utc>) -> Result<String> {
+    let bundle = Bundle {
+        exported: now,
+        state,
+    };
+
+    Ok(match format {
+        Format::Json => serde_json::to_string_pretty(&bundle)?,
+        // Going through a TOML value first sorts plain values before tables, which is required by
+        // the format.
+        Format::Toml => toml::to_string_pretty(&toml::Value::try_from(&bundle)?)?,
+    })
+}
+
+/// Parse a bundle of the given format and migrate the contained state to the current version.
+pub fn parse(content: &str, format: Format) -> Result<State> {
+    let mut bundle = match format {
+        Format::Json => serde_json::from_str::<Value>(content)?,
+        Format::Toml => toml::from_str::<Value>(content)?,
+    };
+
+    let state = bundle
+        .get_mut("state")
+        .context("the bundle doesn't contain any state")?;
+    settings::migrate_state(state)?;
+
+    serde_json::from_value(state.take()).map_err(Into::into)
+}
+
+/// Read and parse a bundle file, detecting its format from the file extension.
+pub fn read(path: &Path) -> Result<State> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("failed reading bundle `{}`", path.display()))?;
+    parse(&content, Format::from_path(path))
+}
+
+/// Create an independent copy of the state.
+fn duplicate(state: &State) -> Result<State> {
+    serde_json::from_value(serde_json::to_value(state)?).map_err(Into::into)
+}
+
+fn same(a: &impl Serialize, b: &impl Serialize) -> bool {
+    serde_json::to_value(a).ok() == serde_json::to_value(b).ok()
+}
+
+/// Merge an imported state into the current one and describe every change. Nothing is removed
+/// from the current state, only added or replaced. In a dry run the current state stays
+/// untouched.
+pub fn merge(state: &mut State, incoming: State, dry_run: bool) -> Result<Vec<String>> {
+    if dry_run {
+        let mut copy = duplicate(state)?;
+        return Ok(merge_into(&mut copy, incoming));
+    }
+
+    Ok(merge_into(state, incoming))
+}
+
+fn merge_into(state: &mut State, incoming: State) -> Vec<String> {
+    let mut changes = Vec::new();
+
+    if !same(&state.schedule, &incoming.schedule) {
+        changes.push(format!(
+            "set schedule to {} - {}",
+            incoming.schedule.format_start(),
+            incoming.schedule.format_finish()
+        ));
+        state.schedule = incoming.schedule;
+    }
+
+    if state.off_days != incoming.off_days {
+        let days = settings::sorted_off_days(&incoming.off_days)
+            .iter()
+            .map(|day| format!("{:?}", day))
+            .collect::<Vec<_>>();
+        changes.push(format!("set off days to {}", days.join(", ")));
+        state.off_days = incoming.off_days;
+    }
+
+    let exceptions = &mut state.schedule_exceptions;
+    for date in incoming.schedule_exceptions.skips {
+        if exceptions.skips.insert(date) {
+            changes.push(format!("skip stream on {}", date));
+        }
+    }
+    for (date, extra) in incoming.schedule_exceptions.extras {
+        if !exceptions
+            .extras
+            .get(&date)
+            .map_or(false, |e| same(e, &extra))
+        {
+            changes.push(format!("set extra stream on {}", date));
+            exceptions.extras.insert(date, extra);
+        }
+    }
+    for (first, last) in incoming.schedule_exceptions.vacations {
+        if exceptions.vacations.insert((first, last)) {
+            changes.push(format!("add vacation from {} to {}", first, last));
+        }
+    }
+
+    let mut commands = incoming.custom_commands.into_iter().collect::<Vec<_>>();
+    commands.sort_by(|a, b| a.0.cmp(&b.0));
+    for (name, command) in commands {
+        match state.custom_commands.get(&name) {
+            Some(current) if same(current, &command) => continue,
+            Some(_) => changes.push(format!("update custom command `{}`", name)),
+            None => changes.push(format!("add custom command `{}`", name)),
+        }
+        state.custom_commands.insert(name, command);
+    }
+
+    // Cooldowns are not worth mentioning, but the latest execution is kept.
+    for (name, time) in incoming.last_executed {
+        let last = state.last_executed.entry(name).or_insert(time);
+        *last = (*last).max(time);
+    }
+
+    let mut timers = incoming.paused_timers.into_iter().collect::<Vec<_>>();
+    timers.sort();
+    for name in timers {
+        let text = format!("pause timer `{}`", name);
+        if state.paused_timers.insert(name) {
+            changes.push(text);
+        }
+    }
+
+    let mut counters = incoming.counters.into_iter().collect::<Vec<_>>();
+    counters.sort_by(|a, b| a.0.cmp(&b.0));
+    for (name, counter) in counters {
+        if state.counters.get(&name).map(|c| c.count) != Some(counter.count) {
+            changes.push(format!("set counter `{}` to {}", name, counter.count));
+            state.counters.insert(name, counter);
+        }
+    }
+
+    for quote in incoming.quotes.all() {
+        match state.quotes.get(quote.number) {
+            Some(current) if current.text == quote.text => continue,
+            Some(_) => changes.push(format!("update quote #{}", quote.number)),
+            None => changes.push(format!("add quote #{}", quote.number)),
+        }
+        state.quotes.insert(quote.clone());
+    }
+
+    let roles = &mut state.reaction_roles;
+    let incoming_roles = incoming.reaction_roles;
+    if incoming_roles.channel.is_some() && incoming_roles.channel != roles.channel {
+        changes.push("move the role message to another channel".to_owned());
+        roles.channel = incoming_roles.channel;
+        roles.message = incoming_roles.message;
+    }
+    for role in incoming_roles.roles {
+        match roles.roles.iter_mut().find(|r| r.emoji == role.emoji) {
+            Some(current) if *current == role => {}
+            Some(current) => {
+                changes.push(format!("update reaction role {}", role.emoji));
+                *current = role;
+            }
+            None => {
+                changes.push(format!("add reaction role {}", role.emoji));
+                roles.roles.push(role);
+            }
+        }
+    }
+
+    changes
+}
+
+/// Write a new backup of the state into the given directory and delete the oldest backups, so
+/// that at most `keep` of them are left.
+pub fn backup(state: &State, dir: &Path, keep: usize, now: DateTime<Utc>) -> Result<PathBuf> {
+    let path = dir.join(format!(
+        "{}{}.json",
+        BACKUP_PREFIX,
+        now.format("%Y%m%d-%H%M%S")
+    ));
+    fs::write(&path, export(state, Format::Json, now)?)?;
+
+    let mut backups = list(dir)?
+        .into_iter()
+        .filter(|name| name.starts_with(BACKUP_PREFIX))
+        .collect::<Vec<_>>();
+    let excess = backups.len().saturating_sub(keep);
+    for name in backups.drain(..excess) {
+        fs::remove_file(dir.join(name))?;
+    }
+
+    Ok(path)
+}
+
+/// List the file names of all bundles in the given directory, sorted by name. Due to the naming
+/// scheme, this sorts backups from oldest to newest.
+pub fn list(dir: &Path) -> Result<Vec<String>> {
+    let mut names = fs::read_dir(dir)?
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            if entry.file_type().ok()?.is_file() {
+                entry.file_name().into_string().ok()
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+    names.sort();
+
+    Ok(names)
+}
+
+/// Periodically back up the state until a shutdown is requested. The first backup is taken right
+/// away.
+pub async fn run(
+    config: Backups,
+    state: AsyncState,
+    dir: PathBuf,
+    clock: impl Clock,
+    mut shutdown: Shutdown,
+) {
+    let period = StdDuration::from_secs(u64::from(config.interval.get()) * 60 * 60);
+    let mut interval = time::interval(period);
+
+    loop {
+        select! {
+            _ = shutdown.recv() => break,
+            _ = interval.tick() => {}
+        }
+
+        let res = backup(&*state.read().await, &dir, config.keep, clock.now());
+        match res {
+            Ok(path) => info!("saved state backup at `{}`", path.display()),
+            Err(e) => error!("failed saving state backup: {:?}", e),
+        }
+    }
+
+    info!("backups shutting down");
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::{
+        settings::{Command, FormatString},
+        Source,
+    };
+
+    fn command(content: &str) -> Command {
+        Command {
+            format: Some(FormatString::Universal(content.to_owned())),
+            ..Command::empty()
+        }
+    }
+
+    fn sample() -> State {
+        let mut state = State::default();
+        state.off_days.insert(Weekday::Wed);
+        state
+            .schedule_exceptions
+            .skips
+            .insert(NaiveDate::from_ymd(2021, 12, 24));
+        state
+            .custom_commands
+            .insert("hello".to_owned(), command("Hello!"));
+        let mut specific = Command::empty();
+        specific.set_content(Some(Source::Discord), "Hello Discord!");
+        specific.set_content(Some(Source::Twitch), "Hello Twitch!");
+        state.custom_commands.insert("greet".to_owned(), specific);
+        state.paused_timers.insert("discord".to_owned());
+        state
+    }
+
+    #[test]
+    fn roundtrip() {
+        let now = Utc.ymd(2021, 6, 1).and_hms(12, 0, 0);
+
+        for &format in &[Format::Json, Format::Toml] {
+            let bundle = export(&sample(), format, now).unwrap();
+            let state = parse(&bundle, format).unwrap();
+
+            assert_eq!(sample().off_days, state.off_days);
+            assert_eq!(
+                sample().schedule_exceptions.skips,
+                state.schedule_exceptions.skips
+            );
+            assert!(state.custom_commands.contains_key("hello"));
+            assert!(
+                matches!(
+                    &state.custom_commands["greet"].format,
+                    Some(FormatString::Specific(formats))
+                        if formats[&Source::Discord] == "Hello Discord!"
+                            && formats[&Source::Twitch] == "Hello Twitch!"
+                ),
+                "{:?} lost the platform specific formats",
+                format
+            );
+            assert_eq!(sample().paused_timers, state.paused_timers);
+        }
+    }
+
+    #[test]
+    fn merge_dry_run() {
+        let mut state = State::default();
+        state
+            .custom_commands
+            .insert("hello".to_owned(), command("Hi!"));
+        state
+            .custom_commands
+            .insert("bye".to_owned(), command("Bye!"));
+
+        let changes = merge(&mut state, sample(), true).unwrap();
+        assert_eq!(
+            vec![
+                "set off days to Wed, Sat, Sun",
+                "skip stream on 2021-12-24",
+                "add custom command `greet`",
+                "update custom command `hello`",
+                "pause timer `discord`",
+            ],
+            changes
+        );
+        assert!(state.paused_timers.is_empty());
+
+        assert_eq!(changes, merge(&mut state, sample(), false).unwrap());
+        assert!(state.paused_timers.contains("discord"));
+        assert!(state.custom_commands.contains_key("bye"));
+        assert!(merge(&mut state, sample(), false).unwrap().is_empty());
+    }
+
+    #[test]
+    fn rotate_backups() {
+        let dir = std::env::temp_dir().join(format!("togglebot-backups-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("export.json"), "{}").unwrap();
+
+        let state = State::default();
+        for hour in 0..4 {
+            backup(&state, &dir, 2, Utc.ymd(2021, 6, 1).and_hms(hour, 0, 0)).unwrap();
+        }
+
+        let names = list(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            vec![
+                "export.json",
+                "state-20210601-020000.json",
+                "state-20210601-030000.json",
+            ],
+            names
+        );
+    }
+}
@@ -0,0 +1,250 @@
+//! Lookup of crates in a crates.io compatible registry.
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use log::info;
+use serde::Deserialize;
+
+use crate::{
+    http::{Http, LinkNotFound},
+    settings::Crates,
+};
+
+/// How long crate details are cached.
+const CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+/// Maximum number of suggestions for a crate that doesn't exist.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Details about a single crate.
+#[derive(Debug, PartialEq, Eq)]
+pub struct CrateInfo {
+    pub name: String,
+    /// Latest stable version, or the latest version if there is no stable one.
+    pub version: String,
+    pub downloads: u64,
+    pub description: Option<String>,
+    /// Link to the crate's documentation.
+    pub docs: String,
+}
+
+/// Outcome of looking up a crate.
+#[derive(Debug, PartialEq, Eq)]
+pub enum CrateLookup {
+    Found(CrateInfo),
+    /// The crate doesn't exist, but there might be some with similar names.
+    NotFound {
+        name: String,
+        suggestions: Vec<String>,
+    },
+}
+
+#[derive(Deserialize)]
+struct CrateResponse {
+    #[serde(rename = "crate")]
+    krate: CrateData,
+}
+
+#[derive(Deserialize)]
+struct CrateData {
+    name: String,
+    max_version: String,
+    max_stable_version: Option<String>,
+    downloads: u64,
+    description: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SearchResponse {
+    crates: Vec<SearchEntry>,
+}
+
+#[derive(Deserialize)]
+struct SearchEntry {
+    name: String,
+}
+
+/// Look up the crate with the given name, or suggest similarly named crates if it doesn't exist.
+/// Other failures of the registry, like timeouts, are returned as errors.
+pub async fn lookup(config: &Crates, name: &str, http: &dyn Http) -> Result<CrateLookup> {
+    let api = config.api.trim_end_matches('/');
+    let name = name.trim();
+
+    match details(api, name, http).await {
+        Ok(krate) => {
+            let version = krate.max_stable_version.unwrap_or(krate.max_version);
+            Ok(CrateLookup::Found(CrateInfo {
+                docs: format!(
+                    "{}/{}/{}",
+                    config.docs.trim_end_matches('/'),
+                    krate.name,
+                    version
+                ),
+                name: krate.name,
+                version,
+                downloads: krate.downloads,
+                description: krate
+                    .description
+                    .map(|description| description.trim().to_owned()),
+            }))
+        }
+        Err(e) if e.is::<LinkNotFound>() => {
+            info!("crate `{}` not found: {}", name, e);
+            let suggestions = match suggest(api, name, http).await {
+                Ok(suggestions) => suggestions,
+                Err(e) => {
+                    info!("failed searching for crates like `{}`: {}", name, e);
+                    Vec::new()
+                }
+            };
+
+            Ok(CrateLookup::NotFound {
+                name: name.to_owned(),
+                suggestions,
+            })
+        }
+        Err(e) => Err(e),
+    }
+}
+
+async fn details(api: &str, name: &str, http: &dyn Http) -> Result<CrateData> {
+    let link = format!("{}/crates/{}", api, encode(name));
+    let document = http.json(&link, CACHE_TTL).await?;
+
+    CrateResponse::deserialize(&*document)
+        .map(|resp| resp.krate)
+        .context("unexpected crate response")
+}
+
+/// Search for crates with names close to the given one, ordered by similarity.
+async fn suggest(api: &str, name: &str, http: &dyn Http) -> Result<Vec<String>> {
+    let link = format!("{}/crates?q={}&per_page=20", api, encode(name));
+    let document = http.json(&link, CACHE_TTL).await?;
+    let search = SearchResponse::deserialize(&*document).context("unexpected search response")?;
+
+    let name = normalize(name);
+    // Allow roughly one typo for every four characters.
+    let max_distance = (name.chars().count() / 4).max(1);
+
+    let mut candidates = search
+        .crates
+        .into_iter()
+        .map(|entry| (distance(&name, &normalize(&entry.name)), entry.name))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect::<Vec<_>>();
+    candidates.sort();
+
+    Ok(candidates
+        .into_iter()
+        .map(|(_, name)| name)
+        .take(MAX_SUGGESTIONS)
+        .collect())
+}
+
+/// Crate names treat `-` and `_` the same and ignore case.
+fn normalize(name: &str) -> String {
+    name.to_lowercase().replace('-', "_")
+}
+
+/// Edit distance between two names, where swapping two neighbouring characters counts as a single
+/// typo like any other edit.
+fn distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+    // Rows of the distance matrix for the previous two and the current character of `a`.
+    let mut before = vec![0; b.len() + 1];
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    let mut current = vec![0; b.len() + 1];
+
+    for i in 1..=a.len() {
+        current[0] = i;
+
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            current[j] = (previous[j] + 1)
+                .min(current[j - 1] + 1)
+                .min(previous[j - 1] + cost);
+
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = current[j].min(before[j - 2] + 1);
+            }
+        }
+
+        std::mem::swap(&mut before, &mut previous);
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
+/// Crate names only contain alphanumeric characters, `-` and `_`, so anything else is dropped
+/// instead of being encoded.
+fn encode(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use super::*;
+    use crate::http::FakeHttp;
+
+    #[test]
+    fn distances() {
+        assert_eq!(0, distance("serde", "serde"));
+        assert_eq!(1, distance("sered", "serde"));
+        assert_eq!(2, distance("sreed", "serde"));
+        assert_eq!(1, distance("tokio", "tokioo"));
+        assert_eq!(5, distance("", "serde"));
+    }
+
+    #[tokio::test]
+    async fn lookup_and_suggest() {
+        let http = FakeHttp {
+            documents: vec![
+                (
+                    "https://crates.io/api/v1/crates/serde".to_owned(),
+                    json! {{ "crate": {
+                        "name": "serde",
+                        "max_version": "1.0.126",
+                        "max_stable_version": "1.0.126",
+                        "downloads": 100_000_000_u64,
+                        "description": "A serialization framework\n",
+                    }}},
+                ),
+                (
+                    "https://crates.io/api/v1/crates?q=sered&per_page=20".to_owned(),
+                    json! {{ "crates": [
+                        { "name": "serde_derive" },
+                        { "name": "serde" },
+                        { "name": "sered-rs" },
+                    ]}},
+                ),
+            ],
+            ..FakeHttp::default()
+        };
+        let config = Crates::default();
+
+        assert_eq!(
+            CrateLookup::Found(CrateInfo {
+                name: "serde".to_owned(),
+                version: "1.0.126".to_owned(),
+                downloads: 100_000_000,
+                description: Some("A serialization framework".to_owned()),
+                docs: "https://docs.rs/serde/1.0.126".to_owned(),
+            }),
+            lookup(&config, "serde", &http).await.unwrap()
+        );
+        assert_eq!(
+            CrateLookup::NotFound {
+                name: "sered".to_owned(),
+                suggestions: vec!["serde".to_owned()],
+            },
+            lookup(&config, "sered", &http).await.unwrap()
+        );
+    }
+}
@@ -0,0 +1,154 @@
+//! Requests to JSON APIs, whose data is filled into the format of a command.
+
+use std::{collections::HashMap, time::Duration};
+
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+
+use super::template;
+use crate::{http::Http, settings::Fetch};
+
+/// Request the data of the fetch and extract all its fields. Fields that don't exist in the
+/// response are left out, so templates can give defaults for them.
+pub async fn run(
+    fetch: &Fetch,
+    args: &[String],
+    http: &dyn Http,
+) -> Result<HashMap<String, String>> {
+    let encoded = args.iter().map(|arg| encode(arg)).collect::<Vec<_>>();
+    let link = template::render(&fetch.url, &encoded)?;
+    let document = http
+        .json(&link, Duration::from_secs(fetch.cache.into()))
+        .await?;
+
+    fetch
+        .fields
+        .iter()
+        .filter_map(|(name, path)| match select(&document, path) {
+            Ok(value) => text(value).map(|text| Ok((name.clone(), text))),
+            Err(e) => Some(Err(e)),
+        })
+        .collect()
+}
+
+/// Select a value with a path like `$.crate.versions[0].num`, where the leading `$` is optional.
+fn select<'a>(value: &'a Value, path: &str) -> Result<Option<&'a Value>> {
+    let path = path.trim();
+    let path = path.strip_prefix('$').unwrap_or(path);
+    let mut current = value;
+
+    for segment in path.split('.').filter(|segment| !segment.is_empty()) {
+        let (key, indices) = match segment.find('[') {
+            Some(pos) => segment.split_at(pos),
+            None => (segment, ""),
+        };
+
+        if !key.is_empty() {
+            current = match current.get(key) {
+                Some(value) => value,
+                None => return Ok(None),
+            };
+        }
+
+        for index in indices.split_terminator(']') {
+            let index = index
+                .strip_prefix('[')
+                .and_then(|index| index.parse::<usize>().ok())
+                .ok_or_else(|| anyhow!("invalid index in path segment `{}`", segment))?;
+            current = match current.get(index) {
+                Some(value) => value,
+                None => return Ok(None),
+            };
+        }
+    }
+
+    Ok(Some(current))
+}
+
+/// Turn a JSON value into text for the template. Lists are joined with commas, while objects and
+/// `null` count as missing.
+fn text(value: Option<&Value>) -> Option<String> {
+    match value? {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        Value::Array(values) => Some(
+            values
+                .iter()
+                .filter_map(|value| text(Some(value)))
+                .collect::<Vec<_>>()
+                .join(", "),
+        ),
+        Value::Object(_) | Value::Null => None,
+    }
+}
+
+/// Percent-encode everything except unreserved characters, so arguments can't change the
+/// structure of a link.
+fn encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                char::from(b).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use super::*;
+    use crate::http::FakeHttp;
+
+    #[test]
+    fn select_paths() {
+        let value = json! {{
+            "crate": { "max_version": "1.0.126", "keywords": ["serde", "serialization"] },
+            "versions": [{ "num": "1.0.126", "downloads": 100 }]
+        }};
+        let get = |path| text(select(&value, path).unwrap());
+
+        assert_eq!(Some("1.0.126".to_owned()), get("$.crate.max_version"));
+        assert_eq!(Some("100".to_owned()), get("versions[0].downloads"));
+        assert_eq!(
+            Some("serde, serialization".to_owned()),
+            get("$.crate.keywords")
+        );
+        assert_eq!(None, get("$.crate.license"));
+        assert_eq!(None, get("$.versions[1].num"));
+        assert!(select(&value, "$.versions[x]").is_err());
+    }
+
+    #[tokio::test]
+    async fn fetch_fields() {
+        let http = FakeHttp {
+            documents: vec![(
+                "https://crates.io/api/v1/crates/a%2Fb".to_owned(),
+                json! {{ "crate": { "max_version": "0.1.0" } }},
+            )],
+            ..FakeHttp::default()
+        };
+        let fetch = Fetch {
+            url: "https://crates.io/api/v1/crates/{1}".to_owned(),
+            fields: vec![
+                ("version".to_owned(), "$.crate.max_version".to_owned()),
+                ("license".to_owned(), "$.crate.license".to_owned()),
+            ]
+            .into_iter()
+            .collect(),
+            error: None,
+            cache: 60,
+        };
+
+        let fields = run(&fetch, &["a/b".to_owned()], &http).await.unwrap();
+        assert_eq!(Some("0.1.0"), fields.get("version").map(String::as_str));
+        assert!(!fields.contains_key("license"));
+
+        assert!(run(&fetch, &["c".to_owned()], &http).await.is_err());
+    }
+}
@@ -0,0 +1,540 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use chrono::{Duration, Utc};
+use dynfmt::{Format as _, SimpleCurlyFormat};
+use log::{info, warn};
+
+use crate::{
+    announce,
+    handler::AsyncState,
+    http::Http,
+    render,
+    settings::{
+        Argument, ArgumentMode, Arity, Change, Command, Counter, CounterState, Embed, EmbedField,
+        Format,
+    },
+    ArgsError, Source, UserResponse,
+};
+
+pub use self::types::Type;
+
+pub mod crates;
+mod fetch;
+pub mod playground;
+mod template;
+mod types;
+
+impl Command {
+    /// Describe how to call the command, like `!crate <name>`.
+    #[must_use]
+    pub fn usage(&self, name: &str) -> String {
+        let mut usage = format!("!{}", name);
+        for arg in self.args.iter().flatten() {
+            usage.push(' ');
+            usage.push_str(&arg.label());
+        }
+
+        usage
+    }
+
+    fn wrong_args(
+        &self,
+        name: &str,
+        argument: Option<&Argument>,
+        reason: String,
+        message: Option<String>,
+    ) -> UserResponse {
+        UserResponse::WrongArgs(ArgsError {
+            command: name.to_owned(),
+            usage: self.usage(name),
+            argument: argument.map(Argument::label),
+            reason,
+            message,
+        })
+    }
+
+    /// Reply for an argument value that didn't pass its type check, using the configured error
+    /// message if there is one.
+    fn invalid_arg(&self, name: &str, arg: &Argument, value: &str) -> UserResponse {
+        let reason = format!("`{}` is not a valid {}", value, arg.label());
+        let message = arg.error.as_ref().and_then(|error| {
+            SimpleCurlyFormat
+                .format(error, [value])
+                .ok()
+                .map(|message| message.to_string())
+        });
+
+        self.wrong_args(name, Some(arg), reason, message)
+    }
+
+    /// Split the given arguments by the declared ones and check each value. Any problem is
+    /// returned as the reply for the user.
+    async fn parse_args(
+        &self,
+        name: &str,
+        args: Option<&str>,
+        http: &dyn Http,
+    ) -> Result<Vec<String>, UserResponse> {
+        let wanted_args = match &self.args {
+            Some(wanted_args) => wanted_args,
+            // Without declared arguments, everything after the command is a single argument.
+            None => {
+                return Ok(args
+                    .map(str::trim)
+                    .filter(|a| !a.is_empty())
+                    .map(|args| vec![args.to_owned()])
+                    .unwrap_or_default())
+            }
+        };
+
+        let mut provided_args = args.unwrap_or_default().split_whitespace();
+        let mut values = Vec::with_capacity(wanted_args.len());
+
+        for arg in wanted_args {
+            let taken = match arg.arity {
+                Arity::Rest => provided_args.by_ref().collect::<Vec<_>>(),
+                _ => provided_args
+                    .by_ref()
+                    .take(arg.ty.wanted_args())
+                    .collect::<Vec<_>>(),
+            };
+
+            let value = if taken.is_empty() {
+                match &arg.arity {
+                    Arity::Required => {
+                        let reason = format!("missing {}", arg.label());
+                        return Err(self.wrong_args(name, Some(arg), reason, None));
+                    }
+                    // Empty values count as missing in templates.
+                    Arity::Optional | Arity::Rest => {
+                        values.push(String::new());
+                        continue;
+                    }
+                    Arity::Default(value) => value.clone(),
+                }
+            } else {
+                taken.join(" ")
+            };
+
+            let checked = match arg.mode {
+                ArgumentMode::Simple => Some(value.clone()).filter(|v| arg.ty.check(v)),
+                ArgumentMode::Test => {
+                    if arg.ty.test(&value, http).await {
+                        Some(value.clone())
+                    } else {
+                        None
+                    }
+                }
+                ArgumentMode::Format => arg.ty.format(&value, http).await,
+            };
+
+            match checked {
+                Some(checked) => values.push(checked),
+                None => return Err(self.invalid_arg(name, arg, &value)),
+            }
+        }
+
+        Ok(values)
+    }
+
+    pub async fn respond(
+        &self,
+        name: &str,
+        args: Option<&str>,
+        state: AsyncState,
+        source: Source,
+        http: &dyn Http,
+    ) -> UserResponse {
+        if !self.platforms.contains(&source) {
+            return UserResponse::Unknown;
+        }
+        if let Some(cooldown) = self.cooldown {
+            let mut state = state.write().await;
+            if let Some(last_executed) = state.last_executed.get(name) {
+                if *last_executed + Duration::seconds(u32::from(cooldown).into()) > Utc::now() {
+                    return UserResponse::Unknown;
+                }
+            }
+            state.last_executed.insert(name.to_string(), Utc::now());
+            if let Err(e) = state.persist(Change::Cooldown(name.into())) {
+                warn!("failed saving cooldown for `{}`: {}", name, e);
+            }
+        }
+        if let Some(format) = &self.format {
+            let format = if let Some(format) = format.get(source) {
+                format
+            } else {
+                return UserResponse::Unknown;
+            };
+            let values = match self.parse_args(name, args, http).await {
+                Ok(values) => values,
+                Err(resp) => return resp,
+            };
+
+            let named = match &self.fetch {
+                Some(fetch) => match fetch::run(fetch, &values, http).await {
+                    Ok(fields) => fields,
+                    Err(e) => {
+                        info!("failed fetching data for `{}`: {}", name, e);
+                        let message = fetch
+                            .error
+                            .as_ref()
+                            .and_then(|error| template::render(error, &values).ok());
+                        let reason = "nothing found".to_owned();
+                        return self.wrong_args(name, None, reason, message);
+                    }
+                },
+                None => HashMap::new(),
+            };
+
+            match render_format(format, |text| template::render_named(text, &values, &named)) {
+                Ok(resp) => resp,
+                Err(e) => {
+                    info!("failed rendering `{}`: {}", name, e);
+                    self.wrong_args(name, None, e.to_string(), None)
+                }
+            }
+        } else {
+            UserResponse::Unknown
+        }
+    }
+}
+
+/// Changes to a counter, that only moderators are allowed to make.
+enum CounterAction {
+    Increment,
+    Decrement,
+    Reset,
+}
+
+impl Counter {
+    pub async fn respond(
+        &self,
+        name: &str,
+        args: Option<&str>,
+        state: AsyncState,
+        source: Source,
+        privileged: bool,
+    ) -> UserResponse {
+        if !self.platforms.contains(&source) {
+            return UserResponse::Unknown;
+        }
+        let format = if let Some(format) = self.format.get(source) {
+            format
+        } else {
+            return UserResponse::Unknown;
+        };
+
+        let action = match args.map(str::trim) {
+            None | Some("") => None,
+            Some("+") => Some(CounterAction::Increment),
+            Some("-") => Some(CounterAction::Decrement),
+            Some("reset") => Some(CounterAction::Reset),
+            Some(action) if privileged => {
+                return UserResponse::WrongArgs(ArgsError {
+                    command: name.to_owned(),
+                    usage: format!("!{} [+|-|reset]", name),
+                    argument: None,
+                    reason: format!("unknown counter action `{}`", action),
+                    message: None,
+                })
+            }
+            Some(_) => return UserResponse::Unknown,
+        };
+        if action.is_some() && !privileged {
+            return UserResponse::Unknown;
+        }
+
+        let now = Utc::now();
+        let mut state = state.write().await;
+        let last_stream = if self.reset_per_stream {
+            announce::last_stream(&state, now).map(|stream| stream.start.with_timezone(&Utc))
+        } else {
+            None
+        };
+
+        let mut count = match state.counters.get(&self.counter) {
+            Some(counter) if last_stream.map_or(true, |start| counter.updated >= start) => {
+                counter.count
+            }
+            _ => 0,
+        };
+
+        if let Some(action) = action {
+            count = match action {
+                CounterAction::Increment => count.saturating_add(1),
+                CounterAction::Decrement => count.saturating_sub(1),
+                CounterAction::Reset => 0,
+            };
+
+            state.counters.insert(
+                self.counter.clone(),
+                CounterState {
+                    count,
+                    updated: now,
+                },
+            );
+            if let Err(e) = state.persist(Change::Counter(self.counter.as_str().into())) {
+                warn!("failed saving counter `{}`: {}", self.counter, e);
+            }
+        }
+
+        let count = count.to_string();
+        render_format(format, |text| Ok(text.replace("{count}", &count)))
+            .unwrap_or(UserResponse::Unknown)
+    }
+}
+
+/// Render a message that isn't the answer to a command, like a timed message, as plain text. It
+/// uses the same template language without any arguments, and embeds are flattened.
+pub fn render_plain(format: Format<'_>) -> Result<String> {
+    let render = |text: &str| template::render(text, &[]);
+
+    match format {
+        Format::Text(text) => render(text),
+        Format::Embed(embed) => render_embed(embed, render).map(|embed| render::flatten(&embed)),
+    }
+}
+
+/// Fill in the placeholders of the format, which for embeds happens in every text of it.
+fn render_format(
+    format: Format<'_>,
+    mut render: impl FnMut(&str) -> Result<String>,
+) -> Result<UserResponse> {
+    match format {
+        Format::Text(text) => render(text).map(UserResponse::Custom),
+        Format::Embed(embed) => render_embed(embed, render).map(UserResponse::Embed),
+    }
+}
+
+fn render_embed(embed: &Embed, mut render: impl FnMut(&str) -> Result<String>) -> Result<Embed> {
+    let mut render_opt = |text: &Option<String>| text.as_deref().map(&mut render).transpose();
+
+    Ok(Embed {
+        title: render_opt(&embed.title)?,
+        description: render_opt(&embed.description)?,
+        url: render_opt(&embed.url)?,
+        color: embed.color,
+        thumbnail: render_opt(&embed.thumbnail)?,
+        footer: render_opt(&embed.footer)?,
+        fields: embed
+            .fields
+            .iter()
+            .map(|field| {
+                Ok(EmbedField {
+                    name: render(&field.name)?,
+                    value: render(&field.value)?,
+                    inline: field.inline,
+                })
+            })
+            .collect::<Result<_>>()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::{
+        http::FakeHttp,
+        settings::{Color, FormatString, SharedState, SqliteStore},
+    };
+
+    fn content(resp: UserResponse) -> Option<String> {
+        match resp {
+            UserResponse::Custom(content) => Some(content),
+            _ => None,
+        }
+    }
+
+    fn state() -> AsyncState {
+        Arc::new(SharedState::load(Box::new(SqliteStore::in_memory().unwrap())).unwrap())
+    }
+
+    #[tokio::test]
+    async fn command_arguments() {
+        let command = Command {
+            args: Some(vec![
+                "who:string".parse().unwrap(),
+                "greeting:string=Hello".parse().unwrap(),
+                "rest:string...".parse().unwrap(),
+            ]),
+            format: Some(FormatString::Universal(
+                "{2}, {1}!{if 3: \" {3}\"}".to_owned(),
+            )),
+            platforms: vec![Source::Twitch],
+            ..Command::empty()
+        };
+        let http = FakeHttp::default();
+        let run = |args: Option<&'static str>| {
+            command.respond("greet", args, state(), Source::Twitch, &http)
+        };
+
+        assert_eq!(
+            Some("Hello, bob!".to_owned()),
+            content(run(Some("bob")).await)
+        );
+        assert_eq!(
+            Some("Hi, bob! nice to see you".to_owned()),
+            content(run(Some("bob Hi nice to see you")).await)
+        );
+
+        match run(None).await {
+            UserResponse::WrongArgs(e) => {
+                assert_eq!("!greet <who> [greeting=Hello] [rest...]", e.usage);
+                assert_eq!(Some("<who>".to_owned()), e.argument);
+                assert_eq!("missing <who>", e.reason);
+            }
+            _ => panic!("expected wrong arguments"),
+        }
+    }
+
+    #[tokio::test]
+    async fn embed_format() {
+        let command: Command = toml::from_str(
+            r##"
+            args = ["name:string"]
+
+            [format]
+            title = "Hello {1}!"
+            color = "#ce422b"
+
+            [[format.fields]]
+            name = "Greeting"
+            value = "{upper: 1}"
+            inline = true
+            "##,
+        )
+        .unwrap();
+        let http = FakeHttp::default();
+
+        match command
+            .respond("hello", Some("bob"), state(), Source::Twitch, &http)
+            .await
+        {
+            UserResponse::Embed(embed) => assert_eq!(
+                Embed {
+                    title: Some("Hello bob!".to_owned()),
+                    color: Some(Color(0x00ce_422b)),
+                    fields: vec![EmbedField {
+                        name: "Greeting".to_owned(),
+                        value: "BOB".to_owned(),
+                        inline: true,
+                    }],
+                    ..Embed::default()
+                },
+                embed
+            ),
+            _ => panic!("expected an embed"),
+        }
+    }
+
+    #[tokio::test]
+    async fn url_arguments() {
+        let command = Command {
+            args: Some(vec![
+                "name:url!https://lib.rs/crates/{}<!>The crate `{}` does not exist!"
+                    .parse()
+                    .unwrap(),
+            ]),
+            format: Some(FormatString::Universal("{}".to_owned())),
+            platforms: vec![Source::Discord],
+            ..Command::empty()
+        };
+        let http = FakeHttp {
+            links: vec!["https://lib.rs/crates/serde".to_owned()],
+            ..FakeHttp::default()
+        };
+        let run = |args: &'static str| {
+            command.respond("crate", Some(args), state(), Source::Discord, &http)
+        };
+
+        assert_eq!(
+            Some("https://lib.rs/crates/serde".to_owned()),
+            content(run("serde").await)
+        );
+        match run("sered").await {
+            UserResponse::WrongArgs(e) => assert_eq!(
+                Some("The crate `sered` does not exist!".to_owned()),
+                e.message
+            ),
+            _ => panic!("expected wrong arguments"),
+        }
+    }
+
+    #[tokio::test]
+    async fn fetch_arguments() {
+        let command: Command = toml::from_str(
+            r#"
+            args = ["name:string"]
+            format = "{1} {version}: {description | no description}"
+
+            [fetch]
+            url = "https://crates.io/api/v1/crates/{1}"
+            fields = { version = "$.crate.max_version", description = "$.crate.description" }
+            error = "The crate `{1}` does not exist!"
+            "#,
+        )
+        .unwrap();
+        let http = FakeHttp {
+            documents: vec![(
+                "https://crates.io/api/v1/crates/serde".to_owned(),
+                serde_json::json! {{ "crate": { "max_version": "1.0.126" } }},
+            )],
+            ..FakeHttp::default()
+        };
+        let run = |args: &'static str| {
+            command.respond("crate", Some(args), state(), Source::Twitch, &http)
+        };
+
+        assert_eq!(
+            Some("serde 1.0.126: no description".to_owned()),
+            content(run("serde").await)
+        );
+        match run("sered").await {
+            UserResponse::WrongArgs(e) => assert_eq!(
+                Some("The crate `sered` does not exist!".to_owned()),
+                e.message
+            ),
+            _ => panic!("expected wrong arguments"),
+        }
+    }
+
+    #[tokio::test]
+    async fn counter_actions() {
+        let state = state();
+        let counter = Counter {
+            counter: "bugs".to_owned(),
+            format: FormatString::Universal("{count} bugs today".to_owned()),
+            reset_per_stream: false,
+            aliases: None,
+            platforms: vec![Source::Twitch],
+        };
+        let run = |args: Option<&'static str>, privileged: bool| {
+            let state = state.clone();
+            let counter = counter.clone();
+            async move {
+                content(
+                    counter
+                        .respond("bugs", args, state, Source::Twitch, privileged)
+                        .await,
+                )
+            }
+        };
+
+        assert_eq!(Some("0 bugs today".to_owned()), run(None, false).await);
+        assert_eq!(Some("1 bugs today".to_owned()), run(Some("+"), true).await);
+        assert_eq!(Some("2 bugs today".to_owned()), run(Some("+"), true).await);
+        assert_eq!(None, run(Some("+"), false).await);
+        assert_eq!(Some("1 bugs today".to_owned()), run(Some("-"), true).await);
+        assert_eq!(
+            Some("0 bugs today".to_owned()),
+            run(Some("reset"), true).await
+        );
+        assert_eq!(Some("0 bugs today".to_owned()), run(Some("-"), true).await);
+    }
+}
@@ -0,0 +1,222 @@
+//! Evaluation of code snippets through a Rust playground compatible endpoint.
+
+use std::{
+    collections::HashMap,
+    sync::{Mutex, PoisonError},
+};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{http::Http, settings::Playground, Source};
+
+/// How the code is run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    /// Run a whole program, which is wrapped in a `main` function if it doesn't have one.
+    Play,
+    /// Evaluate an expression and print its debug representation.
+    Eval,
+}
+
+/// Result of running a snippet.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Evaluation {
+    /// Whether the code compiled and ran successfully.
+    pub success: bool,
+    /// Output of the program, or the compiler errors if it failed.
+    pub output: String,
+    /// Whether the output was cut off to fit into a chat message.
+    pub truncated: bool,
+}
+
+#[derive(Deserialize)]
+struct ExecuteResponse {
+    success: bool,
+    #[serde(default)]
+    stdout: String,
+    #[serde(default)]
+    stderr: String,
+}
+
+/// Per-user cooldowns, so a single user can't keep the evaluator busy. They can be shared between
+/// several commands that run at the same time.
+#[derive(Default)]
+pub struct Cooldowns {
+    last: Mutex<HashMap<(Source, String), DateTime<Utc>>>,
+}
+
+impl Cooldowns {
+    /// Check whether the user may run code now, and if so start a new cooldown for them.
+    pub fn start(&self, source: Source, user: &str, cooldown: u32, now: DateTime<Utc>) -> bool {
+        let cooldown = Duration::seconds(cooldown.into());
+        let mut last = self.last.lock().unwrap_or_else(PoisonError::into_inner);
+        last.retain(|_, last| *last + cooldown > now);
+
+        let key = (source, user.to_lowercase());
+        if last.contains_key(&key) {
+            return false;
+        }
+
+        last.insert(key, now);
+        true
+    }
+}
+
+/// Take the code out of a message, that may be wrapped in a fenced code block with an optional
+/// language, or in inline code.
+#[must_use]
+pub fn extract(message: &str) -> &str {
+    let message = message.trim();
+
+    if let Some(block) = message
+        .strip_prefix("```")
+        .and_then(|block| block.strip_suffix("```"))
+    {
+        return match block.split_once('\n') {
+            Some((lang, code)) if lang.chars().all(|c| c.is_ascii_alphanumeric()) => code.trim(),
+            _ => block.trim(),
+        };
+    }
+
+    message
+        .strip_prefix('`')
+        .and_then(|code| code.strip_suffix('`'))
+        .unwrap_or(message)
+        .trim()
+}
+
+/// Send the code to the playground and collect its output.
+pub async fn run(
+    config: &Playground,
+    mode: Mode,
+    code: &str,
+    http: &dyn Http,
+) -> Result<Evaluation> {
+    let body = json! {{
+        "channel": "stable",
+        "mode": "debug",
+        "edition": config.edition,
+        "crateType": "bin",
+        "tests": false,
+        "backtrace": false,
+        "code": wrap(mode, code),
+    }};
+
+    let resp = http.post_json(&config.url, &body).await?;
+    let resp = ExecuteResponse::deserialize(&resp).context("unexpected playground response")?;
+
+    let output = if resp.success {
+        resp.stdout
+    } else {
+        compiler_output(&resp.stderr)
+    };
+    let (output, truncated) = truncate(output.trim(), config.max_output);
+
+    Ok(Evaluation {
+        success: resp.success,
+        output,
+        truncated,
+    })
+}
+
+fn wrap(mode: Mode, code: &str) -> String {
+    match mode {
+        Mode::Play if code.contains("fn main") => code.to_owned(),
+        Mode::Play => format!("fn main() {{\n{}\n}}", code),
+        Mode::Eval => format!(
+            "fn main() {{\n    println!(\"{{:?}}\", {{\n{}\n    }});\n}}",
+            code
+        ),
+    }
+}
+
+/// Remove cargo's progress lines from the compiler output, leaving only the actual messages.
+fn compiler_output(stderr: &str) -> String {
+    stderr
+        .lines()
+        .filter(|line| {
+            let line = line.trim_start();
+            !["Compiling ", "Finished ", "Running "]
+                .iter()
+                .any(|prefix| line.starts_with(prefix))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Cut the output down to the maximum number of characters.
+fn truncate(output: &str, max: usize) -> (String, bool) {
+    match output.char_indices().nth(max) {
+        Some((pos, _)) => (format!("{}…", &output[..pos]), true),
+        None => (output.to_owned(), false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::http::FakeHttp;
+
+    #[test]
+    fn extract_code() {
+        assert_eq!("1 + 2", extract(" 1 + 2 "));
+        assert_eq!("1 + 2", extract("`1 + 2`"));
+        assert_eq!("let a = 1;", extract("```rust\nlet a = 1;\n```"));
+        assert_eq!("let a = 1;", extract("```\nlet a = 1;\n```"));
+        assert_eq!("let a = 1;", extract("```let a = 1;```"));
+    }
+
+    #[test]
+    fn user_cooldowns() {
+        let cooldowns = Cooldowns::default();
+        let start = Utc.ymd(2021, 6, 1).and_hms(12, 0, 0);
+        let at = |seconds| start + Duration::seconds(seconds);
+
+        assert!(cooldowns.start(Source::Twitch, "togglebit", 30, at(0)));
+        assert!(!cooldowns.start(Source::Twitch, "ToggleBit", 30, at(10)));
+        assert!(cooldowns.start(Source::Discord, "togglebit", 30, at(10)));
+        assert!(cooldowns.start(Source::Twitch, "dnaka91", 30, at(10)));
+        assert!(cooldowns.start(Source::Twitch, "togglebit", 30, at(30)));
+    }
+
+    #[tokio::test]
+    async fn run_code() {
+        let http = FakeHttp {
+            documents: vec![(
+                "https://play.rust-lang.org/execute".to_owned(),
+                json! {{
+                    "success": false,
+                    "stdout": "",
+                    "stderr": "   Compiling playground v0.0.1 (/playground)\n\
+                               error[E0425]: cannot find value `b` in this scope\n",
+                }},
+            )],
+            ..FakeHttp::default()
+        };
+        let config = Playground {
+            max_output: 20,
+            ..Playground::default()
+        };
+
+        assert_eq!(
+            Evaluation {
+                success: false,
+                output: "error[E0425]: cannot…".to_owned(),
+                truncated: true,
+            },
+            run(&config, Mode::Eval, "b", &http).await.unwrap()
+        );
+
+        let posted = http.posted.lock().unwrap();
+        assert_eq!(
+            "fn main() {\n    println!(\"{:?}\", {\nb\n    });\n}",
+            posted[0]["code"]
+        );
+    }
+}
@@ -0,0 +1,351 @@
+//! Small template language for command responses.
+//!
+//! Placeholders are wrapped in curly braces and literal braces are escaped as `{{` and `}}`:
+//!
+//! - `{}` inserts the next argument and `{2}` the second one.
+//! - `{version}` inserts a named value, like the fields that a command fetched from an API.
+//! - `{1 | "chat"}` inserts the first argument or `chat` if it wasn't given.
+//! - `{random: a | b | c}` picks one of the options and `{random: 1..=100}` a number in the range.
+//! - `{if 1: given | missing}` picks a branch depending on whether the first argument was given.
+//! - `{upper: ...}`, `{lower: ...}` and `{capitalize: ...}` change the case of the inner
+//!   placeholder, like `{upper: 1 | "chat"}`.
+//!
+//! Options, branches and defaults can contain further placeholders.
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail, ensure, Result};
+use rand::{seq::SliceRandom, Rng};
+
+/// Render the template with the given arguments, where empty arguments count as missing.
+pub fn render(template: &str, args: &[String]) -> Result<String> {
+    render_named(template, args, &HashMap::new())
+}
+
+/// Render the template like [`render`], with additional named values.
+pub fn render_named(
+    template: &str,
+    args: &[String],
+    named: &HashMap<String, String>,
+) -> Result<String> {
+    render_with(template, args, named, &mut rand::thread_rng())
+}
+
+/// Render the template like [`render_named`], but with a custom source of randomness.
+pub fn render_with(
+    template: &str,
+    args: &[String],
+    named: &HashMap<String, String>,
+    rng: &mut impl Rng,
+) -> Result<String> {
+    Renderer {
+        args,
+        named,
+        next: 0,
+        rng,
+    }
+    .template(template)
+}
+
+struct Renderer<'a, R> {
+    args: &'a [String],
+    named: &'a HashMap<String, String>,
+    /// Index of the argument that is inserted by the next `{}`.
+    next: usize,
+    rng: &'a mut R,
+}
+
+impl<'a, R: Rng> Renderer<'a, R> {
+    fn template(&mut self, template: &str) -> Result<String> {
+        let mut output = String::with_capacity(template.len());
+        let mut rest = template;
+
+        while let Some(pos) = rest.find(|c| c == '{' || c == '}') {
+            output.push_str(&rest[..pos]);
+            let tail = &rest[pos..];
+
+            if tail.starts_with("{{") || tail.starts_with("}}") {
+                output.push_str(&tail[..1]);
+                rest = &tail[2..];
+                continue;
+            }
+            ensure!(tail.starts_with('{'), "unmatched `}}` in template");
+
+            let end = closing(tail)?;
+            output.push_str(&self.placeholder(tail[1..end].trim())?);
+            rest = &tail[end + 1..];
+        }
+
+        output.push_str(rest);
+        Ok(output)
+    }
+
+    fn placeholder(&mut self, body: &str) -> Result<String> {
+        if let Some((head, rest)) = body.split_once(':') {
+            match head.trim() {
+                "random" => return self.random(rest),
+                "upper" => return Ok(self.operand(rest)?.to_uppercase()),
+                "lower" => return Ok(self.operand(rest)?.to_lowercase()),
+                "capitalize" => return Ok(capitalize(&self.operand(rest)?)),
+                head => {
+                    if let Some(arg) = head.strip_prefix("if ") {
+                        return self.conditional(arg, rest);
+                    }
+                }
+            }
+        }
+
+        let mut parts = split_top(body, '|').into_iter();
+        let arg = parts.next().unwrap_or_default().trim();
+        let default = parts.next();
+        ensure!(
+            parts.next().is_none(),
+            "too many defaults in `{{{}}}`",
+            body
+        );
+
+        let value = if arg.is_empty() {
+            self.next += 1;
+            self.arg_at(self.next)
+        } else {
+            self.arg(arg)?
+        };
+
+        match (value, default) {
+            (Some(value), _) => Ok(value.to_owned()),
+            (None, Some(default)) => self.text(default),
+            (None, None) => Err(anyhow!("missing argument for `{{{}}}`", body)),
+        }
+    }
+
+    /// The input of a case transform, which is either another placeholder body like `1 | "chat"`
+    /// or template text like `{random: a | b}`.
+    fn operand(&mut self, input: &str) -> Result<String> {
+        let input = input.trim();
+        if input.starts_with('{') || input.starts_with('"') {
+            self.text(input)
+        } else {
+            self.placeholder(input)
+        }
+    }
+
+    /// Get the argument with the given 1-based number or the named value, if it was given.
+    fn arg(&self, number: &str) -> Result<Option<&'a str>> {
+        let number = number.trim();
+        if number.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+            return Ok(self
+                .named
+                .get(number)
+                .map(String::as_str)
+                .filter(|value| !value.is_empty()));
+        }
+
+        let number = number
+            .parse::<usize>()
+            .map_err(|_| anyhow!("`{}` is not a valid argument number", number))?;
+        ensure!(number > 0, "argument numbers start at 1");
+
+        Ok(self.arg_at(number))
+    }
+
+    fn arg_at(&self, number: usize) -> Option<&'a str> {
+        self.args
+            .get(number - 1)
+            .map(String::as_str)
+            .filter(|arg| !arg.is_empty())
+    }
+
+    fn random(&mut self, options: &str) -> Result<String> {
+        let options = split_top(options, '|');
+
+        if let [option] = options.as_slice() {
+            if let Some((low, high, inclusive)) = parse_range(option) {
+                ensure!(
+                    low < high || (inclusive && low == high),
+                    "the range `{}` is empty",
+                    option.trim()
+                );
+                let value = if inclusive {
+                    self.rng.gen_range(low..=high)
+                } else {
+                    self.rng.gen_range(low..high)
+                };
+                return Ok(value.to_string());
+            }
+        }
+
+        let option = options
+            .choose(&mut *self.rng)
+            .copied()
+            .ok_or_else(|| anyhow!("`random` needs at least one option"))?;
+        self.text(option)
+    }
+
+    fn conditional(&mut self, arg: &str, branches: &str) -> Result<String> {
+        let mut branches = split_top(branches, '|').into_iter();
+        let given = branches.next().unwrap_or_default();
+        let missing = branches.next().unwrap_or_default();
+        ensure!(branches.next().is_none(), "`if` takes at most two branches");
+
+        if self.arg(arg)?.is_some() {
+            self.text(given)
+        } else {
+            self.text(missing)
+        }
+    }
+
+    /// Render a piece of template text, optionally wrapped in double quotes to keep surrounding
+    /// whitespace.
+    fn text(&mut self, text: &str) -> Result<String> {
+        let text = text.trim();
+        let text = text
+            .strip_prefix('"')
+            .and_then(|text| text.strip_suffix('"'))
+            .unwrap_or(text);
+
+        self.template(text)
+    }
+}
+
+/// Find the position of the brace that closes the one at the start of the input.
+fn closing(input: &str) -> Result<usize> {
+    let mut depth = 0_usize;
+    let mut quoted = false;
+
+    for (i, c) in input.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '{' if !quoted => depth += 1,
+            '}' if !quoted => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(i);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    bail!("unclosed `{{` in template")
+}
+
+/// Split the input at each separator that is neither quoted nor inside a nested placeholder.
+fn split_top(input: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0_usize;
+    let mut quoted = false;
+    let mut start = 0;
+
+    for (i, c) in input.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '{' if !quoted => depth += 1,
+            '}' if !quoted => depth = depth.saturating_sub(1),
+            c if c == separator && !quoted && depth == 0 => {
+                parts.push(&input[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+
+    parts.push(&input[start..]);
+    parts
+}
+
+/// Parse a number range like `1..10` or `1..=10`, returning the bounds and whether the upper bound
+/// is included.
+fn parse_range(input: &str) -> Option<(i64, i64, bool)> {
+    let (low, high) = input.trim().split_once("..")?;
+    let (high, inclusive) = match high.strip_prefix('=') {
+        Some(high) => (high, true),
+        None => (high, false),
+    };
+
+    Some((
+        low.trim().parse().ok()?,
+        high.trim().parse().ok()?,
+        inclusive,
+    ))
+}
+
+fn capitalize(value: &str) -> String {
+    let mut chars = value.chars();
+    chars.next().map_or_else(String::new, |first| {
+        first.to_uppercase().chain(chars).collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    fn render(template: &str, args: &[&str]) -> Result<String> {
+        let args = args.iter().map(|&arg| arg.to_owned()).collect::<Vec<_>>();
+        let named = [("version", "1.0.0")]
+            .iter()
+            .map(|&(name, value)| (name.to_owned(), value.to_owned()))
+            .collect();
+        render_with(template, &args, &named, &mut StdRng::seed_from_u64(0))
+    }
+
+    #[test]
+    fn arguments() {
+        assert_eq!("a, b!", render("{}, {}!", &["a", "b"]).unwrap());
+        assert_eq!("b a", render("{2} {1}", &["a", "b"]).unwrap());
+        assert_eq!("{a}", render("{{{}}}", &["a"]).unwrap());
+        assert!(render("{}, {}!", &["a"]).is_err());
+        assert!(render("{0}", &["a"]).is_err());
+        assert!(render("{", &[]).is_err());
+    }
+
+    #[test]
+    fn named_values() {
+        assert_eq!("serde 1.0.0", render("{1} {version}", &["serde"]).unwrap());
+        assert_eq!("none", render("{license | none}", &[]).unwrap());
+        assert!(render("{license}", &[]).is_err());
+    }
+
+    #[test]
+    fn defaults() {
+        let template = r#"Oh {1 | "togglebit"}, you lark!"#;
+        assert_eq!("Oh togglebit, you lark!", render(template, &[]).unwrap());
+        assert_eq!(
+            "Oh dnaka91, you lark!",
+            render(template, &["dnaka91"]).unwrap()
+        );
+        assert_eq!("Oh chat", render("Oh {1 | {2 | chat}}", &[]).unwrap());
+    }
+
+    #[test]
+    fn conditionals_and_case() {
+        let template = r#"{if 1: "Hi {1}" | Hi everyone}"#;
+        assert_eq!("Hi everyone", render(template, &[]).unwrap());
+        assert_eq!("Hi bob", render(template, &["bob"]).unwrap());
+
+        assert_eq!("BOB", render("{upper: 1}", &["bob"]).unwrap());
+        assert_eq!("Chat", render(r#"{capitalize: 1 | "chat"}"#, &[]).unwrap());
+        assert_eq!("bob", render("{lower: {}}", &["BOB"]).unwrap());
+        assert_eq!("HI", render("{upper: {random: hi}}", &[]).unwrap());
+    }
+
+    #[test]
+    fn random() {
+        for _ in 0..10 {
+            let value = render("{random: a | b | {1}}", &["c"]).unwrap();
+            assert!(["a", "b", "c"].contains(&value.as_str()));
+
+            let value = render("{random: 1..=3}", &[])
+                .unwrap()
+                .parse::<i64>()
+                .unwrap();
+            assert!((1..=3).contains(&value));
+        }
+
+        assert_eq!("5", render("{random: 5..=5}", &[]).unwrap());
+        assert!(render("{random: 5..5}", &[]).is_err());
+    }
+}
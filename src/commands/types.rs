@@ -0,0 +1,353 @@
+//! Types of command arguments and how their values are checked and formatted.
+
+use std::fmt::{self, Display};
+
+use dynfmt::{Format, SimpleCurlyFormat};
+use lazy_static::lazy_static;
+use regex::Regex;
+use reqwest::Url;
+
+use crate::http::Http;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Type {
+    /// Any text, taking the given count of words.
+    String(usize),
+    /// A whole number within the inclusive bounds, written as `int`, `int(1..100)` or
+    /// `int(1..=100)`.
+    Int { min: Option<i64>, max: Option<i64> },
+    /// One of a fixed set of words, ignoring case, written as `choice(a|b|c)`.
+    Choice(Vec<String>),
+    /// A Discord mention like `<@123>` or a Twitch user name, optionally prefixed with `@`.
+    User,
+    /// A duration like `5m`, `1h30m` or `2d`.
+    Duration,
+    /// A link, either given directly by the user or created from a pattern like
+    /// `https://lib.rs/crates/{}`.
+    Url(Option<String>),
+}
+
+impl Type {
+    /// Parse a type from its name, the parameters in parentheses, the count of words it takes and
+    /// the argument for testing or formatting values.
+    pub fn parse(
+        s: &str,
+        params: Option<&str>,
+        count: Option<usize>,
+        arg: Option<&str>,
+    ) -> Result<Self, String> {
+        if count.is_some() && s != "string" {
+            return Err(format!("the `{}` type always takes a single word", s));
+        }
+        if params.is_some() && !matches!(s, "int" | "choice") {
+            return Err(format!("the `{}` type doesn't take any parameters", s));
+        }
+
+        Ok(match s {
+            "string" => Self::String(count.unwrap_or(1).max(1)),
+            "int" => {
+                let (min, max) = params.map_or(Ok((None, None)), parse_bounds)?;
+                Self::Int { min, max }
+            }
+            "choice" => {
+                let choices = params
+                    .unwrap_or_default()
+                    .split('|')
+                    .map(str::trim)
+                    .filter(|choice| !choice.is_empty())
+                    .map(ToOwned::to_owned)
+                    .collect::<Vec<_>>();
+                if choices.is_empty() {
+                    return Err("the `choice` type needs at least one option".to_owned());
+                }
+                Self::Choice(choices)
+            }
+            "user" => Self::User,
+            "duration" => Self::Duration,
+            "url" => Self::Url(arg.filter(|arg| !arg.is_empty()).map(ToOwned::to_owned)),
+            _ => return Err(format!("unknown argument type `{}`", s)),
+        })
+    }
+
+    /// Name of the type as written in argument specs.
+    #[must_use]
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::String(_) => "string",
+            Self::Int { .. } => "int",
+            Self::Choice(_) => "choice",
+            Self::User => "user",
+            Self::Duration => "duration",
+            Self::Url(_) => "url",
+        }
+    }
+
+    pub fn wanted_args(&self) -> usize {
+        match self {
+            Self::String(s) => *s,
+            _ => 1,
+        }
+    }
+
+    /// Check that the value has the right shape for the type, without any further tests like
+    /// reaching a link.
+    #[must_use]
+    pub fn check(&self, s: &str) -> bool {
+        self.normalize(s).is_some()
+    }
+
+    /// Check the value and run any further tests of the type, like whether a link exists.
+    pub async fn test(&self, s: &str, http: &dyn Http) -> bool {
+        match self {
+            Self::Url(_) => match self.normalize(s) {
+                Some(link) => http.reachable(&link).await,
+                None => false,
+            },
+            _ => self.check(s),
+        }
+    }
+
+    /// Test the value and bring it into its canonical form, like a plain user name or a full
+    /// link.
+    pub async fn format(&self, s: &str, http: &dyn Http) -> Option<String> {
+        if self.test(s, http).await {
+            self.normalize(s)
+        } else {
+            None
+        }
+    }
+
+    fn normalize(&self, s: &str) -> Option<String> {
+        match self {
+            Self::String(_) => Some(s.to_owned()),
+            Self::Int { min, max } => {
+                let value = s.parse::<i64>().ok()?;
+                let in_bounds =
+                    min.map_or(true, |min| value >= min) && max.map_or(true, |max| value <= max);
+                in_bounds.then(|| value.to_string())
+            }
+            Self::Choice(choices) => choices
+                .iter()
+                .find(|choice| choice.eq_ignore_ascii_case(s))
+                .cloned(),
+            Self::User => normalize_user(s),
+            Self::Duration => parse_duration(s).map(format_duration),
+            Self::Url(Some(pattern)) => SimpleCurlyFormat
+                .format(pattern, &[s])
+                .ok()
+                .and_then(|link| Url::parse(&link).ok())
+                .map(Url::into_string),
+            Self::Url(None) => Url::parse(s)
+                .ok()
+                .filter(|url| matches!(url.scheme(), "http" | "https"))
+                .map(Url::into_string),
+        }
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::String(1) => f.write_str("string"),
+            Self::String(count) => write!(f, "string{}", count),
+            Self::Int {
+                min: None,
+                max: None,
+            } => f.write_str("int"),
+            Self::Int { min, max } => {
+                f.write_str("int(")?;
+                if let Some(min) = min {
+                    write!(f, "{}", min)?;
+                }
+                f.write_str("..")?;
+                if let Some(max) = max {
+                    write!(f, "={}", max)?;
+                }
+                f.write_str(")")
+            }
+            Self::Choice(choices) => write!(f, "choice({})", choices.join("|")),
+            Self::User => f.write_str("user"),
+            Self::Duration => f.write_str("duration"),
+            Self::Url(_) => f.write_str("url"),
+        }
+    }
+}
+
+/// Parse number bounds like `1..100`, `1..=100` or `1..` into inclusive minimum and maximum.
+fn parse_bounds(s: &str) -> Result<(Option<i64>, Option<i64>), String> {
+    let invalid = || format!("invalid number range `{}`", s);
+    let (min, max) = s.split_once("..").ok_or_else(invalid)?;
+    let (max, inclusive) = match max.strip_prefix('=') {
+        Some(max) => (max, true),
+        None => (max, false),
+    };
+
+    let parse = |value: &str| {
+        let value = value.trim();
+        if value.is_empty() {
+            Ok(None)
+        } else {
+            value.parse::<i64>().map(Some).map_err(|_| invalid())
+        }
+    };
+    let min = parse(min)?;
+    let max = match parse(max)? {
+        Some(max) if !inclusive => Some(max.checked_sub(1).ok_or_else(invalid)?),
+        max => max,
+    };
+
+    if let (Some(min), Some(max)) = (min, max) {
+        if min > max {
+            return Err(format!("the number range `{}` is empty", s));
+        }
+    }
+
+    Ok((min, max))
+}
+
+/// Bring a Discord mention into the form `<@123>` or a Twitch user name into the user's login,
+/// which is the lowercase name without the leading `@`. Discord shows mentions with the display
+/// name of the user, while Twitch chat only knows the login.
+fn normalize_user(s: &str) -> Option<String> {
+    lazy_static! {
+        static ref MENTION: Regex = Regex::new(r"^<@!?(\d+)>$").expect("mention regex to be valid");
+        static ref NAME: Regex =
+            Regex::new(r"^@?([a-zA-Z0-9_]{1,25})$").expect("name regex to be valid");
+    }
+
+    if let Some(captures) = MENTION.captures(s) {
+        return Some(format!("<@{}>", &captures[1]));
+    }
+
+    NAME.captures(s).map(|captures| captures[1].to_lowercase())
+}
+
+/// Parse a duration made up of numbers with units like `1h30m`. Supported units are `d`, `h`,
+/// `m` and `s`.
+fn parse_duration(s: &str) -> Option<chrono::Duration> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"^(?:\d+[dhms])+$").expect("duration regex to be valid");
+        static ref PART: Regex =
+            Regex::new(r"(\d+)([dhms])").expect("duration part regex to be valid");
+    }
+
+    if !RE.is_match(s) {
+        return None;
+    }
+
+    let mut seconds = 0_i64;
+    for part in PART.captures_iter(s) {
+        let value = part[1].parse::<i64>().ok()?;
+        let unit = match &part[2] {
+            "d" => 24 * 60 * 60,
+            "h" => 60 * 60,
+            "m" => 60,
+            _ => 1,
+        };
+        seconds = seconds.checked_add(value.checked_mul(unit)?)?;
+    }
+
+    (seconds > 0).then(|| chrono::Duration::seconds(seconds))
+}
+
+/// Format a duration in its canonical form like `1h 30m`.
+fn format_duration(duration: chrono::Duration) -> String {
+    let total = duration.num_seconds();
+    let parts = [
+        (total / (24 * 60 * 60), 'd'),
+        (total / (60 * 60) % 24, 'h'),
+        (total / 60 % 60, 'm'),
+        (total % 60, 's'),
+    ];
+
+    parts
+        .iter()
+        .filter(|(value, _)| *value > 0)
+        .map(|(value, unit)| format!("{}{}", value, unit))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn normalize(ty: &str, params: Option<&str>, value: &str) -> Option<String> {
+        Type::parse(ty, params, None, None)
+            .unwrap()
+            .normalize(value)
+    }
+
+    #[test]
+    fn int_bounds() {
+        assert_eq!(Some("5".to_owned()), normalize("int", None, "+5"));
+        assert_eq!(None, normalize("int", None, "five"));
+        assert_eq!(
+            Some("99".to_owned()),
+            normalize("int", Some("1..100"), "99")
+        );
+        assert_eq!(None, normalize("int", Some("1..100"), "100"));
+        assert_eq!(
+            Some("100".to_owned()),
+            normalize("int", Some("1..=100"), "100")
+        );
+        assert_eq!(None, normalize("int", Some("1.."), "0"));
+        assert!(Type::parse("int", Some("5..5"), None, None).is_err());
+
+        let ty = Type::parse("int", Some("1..100"), None, None).unwrap();
+        assert_eq!("int(1..=99)", ty.to_string());
+    }
+
+    #[test]
+    fn choices_and_users() {
+        assert_eq!(
+            Some("Rock".to_owned()),
+            normalize("choice", Some("Rock|Paper"), "rock")
+        );
+        assert_eq!(None, normalize("choice", Some("Rock|Paper"), "scissors"));
+        assert!(Type::parse("choice", Some("|"), None, None).is_err());
+
+        assert_eq!(
+            Some("<@123>".to_owned()),
+            normalize("user", None, "<@!123>")
+        );
+        assert_eq!(
+            Some("togglebit".to_owned()),
+            normalize("user", None, "@ToggleBit")
+        );
+        assert_eq!(None, normalize("user", None, "not a user"));
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(Some("5m".to_owned()), normalize("duration", None, "5m"));
+        assert_eq!(
+            Some("1h 30m".to_owned()),
+            normalize("duration", None, "1h30m")
+        );
+        assert_eq!(
+            Some("1h 30m".to_owned()),
+            normalize("duration", None, "90m")
+        );
+        assert_eq!(None, normalize("duration", None, "0s"));
+        assert_eq!(None, normalize("duration", None, "5 minutes"));
+    }
+
+    #[test]
+    fn urls() {
+        assert_eq!(
+            Some("https://lib.rs/".to_owned()),
+            normalize("url", None, "https://lib.rs")
+        );
+        assert_eq!(None, normalize("url", None, "ftp://lib.rs"));
+        assert_eq!(None, normalize("url", None, "lib.rs"));
+
+        let ty = Type::parse("url", None, None, Some("https://lib.rs/crates/{}")).unwrap();
+        assert_eq!(
+            Some("https://lib.rs/crates/serde".to_owned()),
+            ty.normalize("serde")
+        );
+    }
+}
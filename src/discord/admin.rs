@@ -1,89 +1,130 @@
 use anyhow::Result;
 use indoc::indoc;
 use twilight_http::Client;
-use twilight_model::channel::Message as ChannelMessage;
-
-use crate::{emojis, Source};
-
-pub async fn help(msg: ChannelMessage, http: Client) -> Result<()> {
-    http.create_message(msg.channel_id)
-        .reply(msg.id)
-        .content(indoc! {"
-            Hey there, I support the following admin commands:
-
-            ```
-            !schedule set [start|finish] <HH:MM[am|pm]> <HH:MM[am|pm]>
-            ```
-            Update the current schedule for either `start` or `finish` with the given \
-            range in 12-hour format like `07:00am 08:00am`.
-
-            ```
-            !off_days [add|remove] <weekday>
-            ```
-            Update the off days by `add`ing or `remove`ing a single weekday like \
-            `Mon` or `tuesday`.
-
-            ```
-            !custom_commands [add|remove] [all|discord|twitch] <name> <content>
-            ```
-            Add or remove a custom command that has fixed content and can be anything. \
-            The command can be modified for all sources or individually. \
-            Command names must start with a lowercase letter, only consist of lowercase \
-            letters, numbers and underscores and must not start with the `!`.
-
-            ```
-            !custom_commands list
-            ```
-            List all currently available custom commands.
-        "})?
-        .await?;
-
-    Ok(())
+
+use crate::{
+    emojis, render,
+    settings::{Command, FormatString, ReactionRoles},
+    StateReport,
+};
+
+use super::Target;
+
+pub async fn help(target: Target, http: Client) -> Result<()> {
+    let content = indoc! {"
+        Hey there, I support the following admin commands, which also work as \
+        `/admin` slash command in any channel:
+
+        ```
+        !schedule set [start|finish] <HH:MM[am|pm]> <HH:MM[am|pm]>
+        ```
+        Update the current schedule for either `start` or `finish` with the given \
+        range in 12-hour format like `07:00am 08:00am`.
+
+        ```
+        !schedule skip <YYYY-MM-DD>
+        !schedule extra <YYYY-MM-DD> <HH:MM> <HH:MM> [\"title\"]
+        !schedule vacation <YYYY-MM-DD>..<YYYY-MM-DD>
+        !schedule remove <YYYY-MM-DD>
+        ```
+        Manage one-off exceptions to the schedule by `skip`ping a single day, adding an \
+        `extra` stream with an optional title or taking a `vacation` over a range of days. \
+        `remove` deletes any exception on the given date. Past exceptions are removed \
+        automatically.
+
+        ```
+        !off_days [add|remove] <weekday>
+        ```
+        Update the off days by `add`ing or `remove`ing a single weekday like \
+        `Mon` or `tuesday`.
+
+        ```
+        !custom_commands [add|edit|remove] [all|discord|twitch] <name> [options] <content>
+        ```
+        Add, edit or remove a custom command. The content is a template that can use \
+        placeholders like `{}` for arguments, `{1 | \"default\"}` or `{random: a | b}` \
+        and can be modified for all sources or individually. \
+        Command names must start with a lowercase letter, only consist of lowercase \
+        letters, numbers and underscores and must not start with the `!`. \
+        Names that are already used by configured commands can't be used.
+
+        Options are given before the content and replace the current setting when editing:
+        `--args <type>` adds an argument like `string`, `string?`, `string=chat` or `string...` \
+        (repeatable, `none` removes all), \
+        `--cooldown <seconds>` sets the cooldown (`0` removes it) and \
+        `--alias <name>` adds an alias (repeatable, `none` removes all). \
+        Wrap values in double quotes if they contain spaces.
+
+        ```
+        !custom_commands list
+        ```
+        List all currently available custom commands.
+
+        ```
+        !timers [pause|resume] <name>
+        !timers list
+        ```
+        Pause or resume one of the configured timed messages, or list all of them.
+
+        ```
+        !quotes delete <number>
+        !quotes edit <number> <text>
+        ```
+        Delete a quote or replace its text. Numbers of deleted quotes are not reused, except
+        for the one of the most recent quote.
+
+        ```
+        !roles channel <channel>
+        !roles add <emoji> <role> <description>
+        !roles remove <emoji>
+        !roles list
+        ```
+        Manage the self-assignable roles, that users get by reacting to the role message with \
+        the role's emoji. The message is posted in the given channel and kept up to date. \
+        Channels and roles are given by their ID.
+
+        ```
+        !state export [json|toml]
+        !state import <file> [dry-run]
+        !state backups
+        ```
+        Export the whole state as a bundle into the backup directory, merge a bundle from \
+        there back into the current state or list all available bundles. A `dry-run` \
+        only shows what an import would change.
+    "};
+
+    target.content(&http, content.to_owned()).await
 }
 
-pub async fn schedule(msg: ChannelMessage, http: Client, res: Result<()>) -> Result<()> {
+pub async fn schedule(target: Target, http: Client, res: Result<()>) -> Result<()> {
     let message = match res {
         Ok(()) => format!("{} schedule updated", emojis::OK_HAND),
         Err(e) => format!("{} some error happened: {}", emojis::COLLISION, e),
     };
 
-    http.create_message(msg.channel_id)
-        .reply(msg.id)
-        .content(message)?
-        .await?;
-
-    Ok(())
+    target.content(&http, message).await
 }
 
-pub async fn off_days(msg: ChannelMessage, http: Client, res: Result<()>) -> Result<()> {
+pub async fn off_days(target: Target, http: Client, res: Result<()>) -> Result<()> {
     let message = match res {
         Ok(()) => format!("{} off days updated", emojis::OK_HAND),
         Err(e) => format!("{} some error happened: {}", emojis::COLLISION, e),
     };
 
-    http.create_message(msg.channel_id)
-        .reply(msg.id)
-        .content(message)?
-        .await?;
-
-    Ok(())
+    target.content(&http, message).await
 }
 
 pub async fn custom_commands(
-    msg: ChannelMessage,
+    target: Target,
     http: Client,
-    res: Result<Option<Vec<(String, Source, String)>>>,
+    res: Result<Option<Vec<(String, Command)>>>,
 ) -> Result<()> {
     let message = match res {
         Ok(Some(list)) => list.into_iter().fold(
             String::from("available custom commands:"),
-            |mut list, (name, source, content)| {
-                list.push_str("\n\n`!");
-                list.push_str(&name);
-                list.push_str("` (");
-                list.push_str(source.as_ref());
-                list.push_str("):\n> ");
-                list.push_str(&content);
+            |mut list, (name, command)| {
+                list.push_str("\n\n");
+                list.push_str(&describe_command(&name, &command));
                 list
             },
         ),
@@ -91,10 +132,121 @@ pub async fn custom_commands(
         Err(e) => format!("{} some error happened: {}", emojis::COLLISION, e),
     };
 
-    http.create_message(msg.channel_id)
-        .reply(msg.id)
-        .content(message)?
-        .await?;
+    target.long(&http, message, "commands.md").await
+}
+
+fn describe_command(name: &str, command: &Command) -> String {
+    let mut description = format!("`!{}`", name);
+
+    if let Some(aliases) = &command.aliases {
+        description.push_str(&format!(" (or `!{}`)", aliases.join("`, `!")));
+    }
+    if let Some(args) = &command.args {
+        let args = args.iter().map(ToString::to_string).collect::<Vec<_>>();
+        description.push_str(&format!(", args: `{}`", args.join("` `")));
+    }
+    if let Some(cooldown) = command.cooldown {
+        description.push_str(&format!(", cooldown: {}s", cooldown));
+    }
+
+    match &command.format {
+        Some(FormatString::Universal(content)) => {
+            description.push_str("\n> ");
+            description.push_str(content);
+        }
+        Some(FormatString::Specific(map)) => {
+            for (source, content) in map {
+                description.push_str(&format!("\n> {}: {}", source.as_ref(), content));
+            }
+        }
+        Some(FormatString::Embed(embed)) => {
+            description.push_str("\n> embed: ");
+            description.push_str(&render::flatten(embed));
+        }
+        None => {}
+    }
+
+    description
+}
+
+pub async fn timers(
+    target: Target,
+    http: Client,
+    res: Result<Option<Vec<(String, bool)>>>,
+) -> Result<()> {
+    let message = match res {
+        Ok(Some(list)) => list.into_iter().fold(
+            String::from("configured timers:"),
+            |mut list, (name, paused)| {
+                list.push_str("\n`");
+                list.push_str(&name);
+                list.push('`');
+                if paused {
+                    list.push_str(" (paused)");
+                }
+                list
+            },
+        ),
+        Ok(None) => format!("{} timers updated", emojis::OK_HAND),
+        Err(e) => format!("{} some error happened: {}", emojis::COLLISION, e),
+    };
+
+    target.content(&http, message).await
+}
+
+pub async fn quotes(target: Target, http: Client, res: Result<()>) -> Result<()> {
+    let message = match res {
+        Ok(()) => format!("{} quotes updated", emojis::OK_HAND),
+        Err(e) => format!("{} some error happened: {}", emojis::COLLISION, e),
+    };
+
+    target.content(&http, message).await
+}
+
+pub async fn roles(target: Target, http: Client, res: Result<Option<ReactionRoles>>) -> Result<()> {
+    let message = match res {
+        Ok(Some(roles)) => {
+            let mut list = match roles.channel {
+                Some(channel) => format!("role message in <#{}>:", channel),
+                None => "no channel set for the role message:".to_owned(),
+            };
+            for role in roles.roles {
+                list.push_str(&format!(
+                    "\n{} role `{}`: {}",
+                    role.emoji, role.role, role.description
+                ));
+            }
+            list
+        }
+        Ok(None) => format!("{} roles updated", emojis::OK_HAND),
+        Err(e) => format!("{} some error happened: {}", emojis::COLLISION, e),
+    };
+
+    target.content(&http, message).await
+}
+
+pub async fn state(target: Target, http: Client, res: Result<StateReport>) -> Result<()> {
+    let message = match res {
+        Ok(StateReport::Backups(names)) if names.is_empty() => "no backups yet".to_owned(),
+        Ok(StateReport::Backups(names)) => format!("available backups:\n`{}`", names.join("`\n`")),
+        Ok(StateReport::Exported(name)) => {
+            format!("{} state exported as `{}`", emojis::OK_HAND, name)
+        }
+        Ok(StateReport::Imported { changes, .. }) if changes.is_empty() => {
+            format!("{} nothing to change", emojis::OK_HAND)
+        }
+        Ok(StateReport::Imported { changes, dry_run }) => format!(
+            "{} {}:\n- {}",
+            emojis::OK_HAND,
+            if dry_run {
+                "the import would make these changes"
+            } else {
+                "state imported with these changes"
+            },
+            changes.join("\n- ")
+        ),
+        Err(e) => format!("{} some error happened: {}", emojis::COLLISION, e),
+    };
 
-    Ok(())
+    target.content(&http, message).await
 }
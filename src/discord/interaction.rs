@@ -0,0 +1,292 @@
+//! Slash command interactions, which the Discord library doesn't support yet. Their events are
+//! read from the raw gateway payloads and answered with plain API requests.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use twilight_http::{
+    request::{Method, Request},
+    routing::Path,
+    Client,
+};
+use twilight_model::{
+    channel::embed::Embed,
+    id::{ApplicationId, GenericId, GuildId},
+    user::User,
+};
+
+/// Interaction type of a used slash command.
+const APPLICATION_COMMAND: u8 = 2;
+/// Callback type that acknowledges an interaction and shows a loading state until the answer is
+/// sent.
+const DEFERRED_CHANNEL_MESSAGE: u8 = 5;
+/// Message flag that makes the answer only visible to the user of the command.
+const EPHEMERAL: u64 = 1 << 6;
+
+/// A used slash command.
+#[derive(Debug, Deserialize)]
+pub struct Interaction {
+    pub id: GenericId,
+    #[serde(rename = "type")]
+    pub kind: u8,
+    pub token: String,
+    pub guild_id: Option<GuildId>,
+    /// The user that used the command in a server.
+    pub member: Option<Member>,
+    /// The user that used the command in a direct message.
+    pub user: Option<User>,
+    pub data: Option<CommandData>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Member {
+    pub user: Option<User>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CommandData {
+    pub name: String,
+    #[serde(default)]
+    pub options: Vec<CommandDataOption>,
+}
+
+/// Value of a single option that the user filled in.
+#[derive(Debug, Deserialize)]
+pub struct CommandDataOption {
+    pub name: String,
+    #[serde(default)]
+    pub value: Value,
+}
+
+/// Definition of a slash command that is registered with Discord.
+#[derive(Debug, Serialize)]
+pub struct Command {
+    pub name: String,
+    pub description: String,
+    pub options: Vec<CommandOption>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CommandOption {
+    #[serde(rename = "type")]
+    pub kind: OptionType,
+    pub name: String,
+    pub description: String,
+    pub required: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub choices: Vec<CommandOptionChoice>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(into = "u8")]
+pub enum OptionType {
+    String,
+    Integer,
+    User,
+}
+
+impl From<OptionType> for u8 {
+    fn from(kind: OptionType) -> Self {
+        match kind {
+            OptionType::String => 3,
+            OptionType::Integer => 4,
+            OptionType::User => 6,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CommandOptionChoice {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Deserialize)]
+struct Dispatch {
+    t: Option<String>,
+    d: Option<Value>,
+}
+
+/// Pick the used slash commands out of the raw gateway payloads.
+pub fn parse(payload: &[u8]) -> Result<Option<Interaction>> {
+    let dispatch = serde_json::from_slice::<Dispatch>(payload)?;
+    let data = match (dispatch.t.as_deref(), dispatch.d) {
+        (Some("INTERACTION_CREATE"), Some(data)) => data,
+        _ => return Ok(None),
+    };
+
+    let interaction = serde_json::from_value::<Interaction>(data)?;
+    if interaction.kind == APPLICATION_COMMAND {
+        Ok(Some(interaction))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Replace all slash commands of the application, either for a single server or globally.
+pub async fn set_commands(
+    http: &Client,
+    application: ApplicationId,
+    guild: Option<u64>,
+    commands: &[Command],
+) -> Result<()> {
+    let (path, path_str) = match guild {
+        Some(guild) => (
+            Path::GuildsId(guild),
+            format!("applications/{}/guilds/{}/commands", application, guild),
+        ),
+        None => (
+            Path::OauthApplicationsMe,
+            format!("applications/{}/commands", application),
+        ),
+    };
+
+    send(http, Method::Put, path, path_str, &commands).await
+}
+
+/// Acknowledge a slash command, so it can be answered later on. Ephemeral answers are only shown
+/// to the user of the command.
+pub async fn defer(http: &Client, interaction: &Interaction, ephemeral: bool) -> Result<()> {
+    #[derive(Serialize)]
+    struct Callback {
+        #[serde(rename = "type")]
+        kind: u8,
+        data: CallbackData,
+    }
+
+    #[derive(Serialize)]
+    struct CallbackData {
+        flags: u64,
+    }
+
+    send(
+        http,
+        Method::Post,
+        Path::WebhooksId(interaction.id.0),
+        format!(
+            "interactions/{}/{}/callback",
+            interaction.id, interaction.token
+        ),
+        &Callback {
+            kind: DEFERRED_CHANNEL_MESSAGE,
+            data: CallbackData {
+                flags: if ephemeral { EPHEMERAL } else { 0 },
+            },
+        },
+    )
+    .await
+}
+
+/// Set the answer of an acknowledged slash command.
+pub async fn answer(
+    http: &Client,
+    application: ApplicationId,
+    token: &str,
+    content: Option<String>,
+    embeds: Vec<Embed>,
+) -> Result<()> {
+    #[derive(Serialize)]
+    struct Answer {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        content: Option<String>,
+        embeds: Vec<Embed>,
+    }
+
+    send(
+        http,
+        Method::Patch,
+        Path::WebhooksIdTokenMessagesId(application.0),
+        format!("webhooks/{}/{}/messages/@original", application, token),
+        &Answer { content, embeds },
+    )
+    .await
+}
+
+/// Send a request with a JSON body. The path is only used to group requests for rate limiting.
+/// Other API features that the Discord library doesn't support yet use it as well.
+pub(super) async fn send(
+    http: &Client,
+    method: Method,
+    path: Path,
+    path_str: String,
+    body: &(impl Serialize + Sync),
+) -> Result<()> {
+    http.verify(Request {
+        body: Some(serde_json::to_vec(body)?),
+        form: None,
+        headers: None,
+        method,
+        path,
+        path_str: path_str.into(),
+    })
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn parse_payloads() {
+        let payload = br#"{"op":0,"s":3,"t":"INTERACTION_CREATE","d":{
+            "id":"10","type":2,"token":"abc","guild_id":"20",
+            "member":{"user":{"id":"30","username":"toggle","discriminator":"0090","avatar":null}},
+            "data":{"id":"40","name":"so","options":[
+                {"name":"user","type":6,"value":"50"},
+                {"name":"times","type":4,"value":3}
+            ]}
+        }}"#;
+
+        let interaction = parse(payload).unwrap().unwrap();
+        assert_eq!(GenericId(10), interaction.id);
+        assert_eq!(
+            "toggle",
+            interaction.member.unwrap().user.unwrap().name.as_str()
+        );
+        let data = interaction.data.unwrap();
+        assert_eq!("so", data.name);
+        assert_eq!(Value::from(3), data.options[1].value);
+
+        assert!(parse(br#"{"op":0,"s":4,"t":"MESSAGE_CREATE","d":{}}"#)
+            .unwrap()
+            .is_none());
+        assert!(parse(br#"{"op":11,"d":null}"#).unwrap().is_none());
+    }
+
+    #[test]
+    fn serialize_commands() {
+        let command = Command {
+            name: "rps".to_owned(),
+            description: "Rock paper scissors".to_owned(),
+            options: vec![CommandOption {
+                kind: OptionType::String,
+                name: "choice".to_owned(),
+                description: "One of the choices".to_owned(),
+                required: true,
+                choices: vec![CommandOptionChoice {
+                    name: "rock".to_owned(),
+                    value: "rock".to_owned(),
+                }],
+            }],
+        };
+
+        assert_eq!(
+            serde_json::json!({
+                "name": "rps",
+                "description": "Rock paper scissors",
+                "options": [{
+                    "type": 3,
+                    "name": "choice",
+                    "description": "One of the choices",
+                    "required": true,
+                    "choices": [{"name": "rock", "value": "rock"}]
+                }]
+            }),
+            serde_json::to_value(&command).unwrap()
+        );
+    }
+}
@@ -1,31 +1,63 @@
 //! Discord service connector that allows to receive commands from Discord servers.
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use chrono::{Duration, SecondsFormat, Utc};
 use futures_util::StreamExt;
 use log::{error, info};
+use serde::Serialize;
 use tokio::sync::oneshot;
 use twilight_gateway::{Event, EventTypeFlags, Intents, Shard};
-use twilight_http::Client;
-use twilight_model::channel::Message as ChannelMessage;
+use twilight_http::{request::Method, routing::Path, Client};
+use twilight_model::{
+    channel::{embed::Embed, Message as ChannelMessage},
+    id::{ApplicationId, ChannelId, GuildId, UserId},
+};
 
 use crate::{
-    settings::Discord, AdminResponse, Message, Queue, Response, Shutdown, Source, UserResponse,
+    emojis,
+    handler::AsyncState,
+    moderation::Violation,
+    render::{self, DISCORD_LIMIT},
+    settings::{Discord, ModerationAction},
+    AdminResponse, CommandList, Message, Outbox, Outgoing, Queue, Response, Shutdown, Source,
+    UserResponse,
 };
 
+use self::interaction::Interaction;
+
 mod admin;
+mod interaction;
+mod roles;
+mod slash;
 mod user;
 
-pub async fn start(config: &Discord, queue: Queue, mut shutdown: Shutdown) -> Result<()> {
+/// Maximum number of messages that a long response is split into. Anything longer is sent as a
+/// file instead.
+const MAX_PARTS: usize = 3;
+/// Longest time in seconds that Discord allows to time out a member for.
+const MAX_TIMEOUT: u32 = 28 * 24 * 60 * 60;
+
+pub async fn start(
+    platform: String,
+    config: &Discord,
+    queue: Queue,
+    mut outbox: Outbox,
+    commands: CommandList,
+    state: AsyncState,
+    mut shutdown: Shutdown,
+) -> Result<()> {
     let http = Client::new(&config.token);
+    let guild = config.guild;
 
     let mut shard = Shard::builder(
         &config.token,
-        Intents::GUILD_MESSAGES | Intents::DIRECT_MESSAGES,
+        Intents::GUILD_MESSAGES | Intents::GUILD_MESSAGE_REACTIONS | Intents::DIRECT_MESSAGES,
     )
     .http_client(http.clone())
     .build();
 
     shard.start().await?;
+    let application = http.current_user_application().await?.id;
 
     let shard_spawn = shard.clone();
 
@@ -36,15 +68,59 @@ pub async fn start(config: &Discord, queue: Queue, mut shutdown: Shutdown) -> Re
         shard_spawn.shutdown();
     });
 
-    let mut events = shard.some_events(EventTypeFlags::READY | EventTypeFlags::MESSAGE_CREATE);
+    // Slash commands only arrive as raw payloads, as the gateway doesn't know their event yet.
+    let mut events = shard.some_events(
+        EventTypeFlags::READY
+            | EventTypeFlags::MESSAGE_CREATE
+            | EventTypeFlags::SHARD_PAYLOAD
+            | EventTypeFlags::REACTION_ADD
+            | EventTypeFlags::REACTION_REMOVE,
+    );
+
+    let http_outbox = http.clone();
 
     tokio::spawn(async move {
+        while let Some(message) = outbox.recv().await {
+            if let Err(e) = handle_outgoing(message, &http_outbox).await {
+                error!("error during outgoing message: {}", e);
+            }
+        }
+    });
+
+    tokio::spawn(async move {
+        let mut synced = false;
+
         while let Some(event) = events.next().await {
             let http = http.clone();
             let queue = queue.clone();
+            let commands = commands.clone();
+            let state = state.clone();
+            let platform = platform.clone();
+
+            if let Event::Ready(_) = &event {
+                // Ready is sent again after reconnecting, but the commands only need one sync task.
+                if !synced {
+                    synced = true;
+                    tokio::spawn(slash::sync(
+                        http.clone(),
+                        application,
+                        guild,
+                        commands.clone(),
+                    ));
+
+                    let (http, state) = (http.clone(), state.clone());
+                    tokio::spawn(async move {
+                        if let Err(e) = roles::sync(&http, &state).await {
+                            error!("failed updating the role message: {}", e);
+                        }
+                    });
+                }
+            }
 
             tokio::spawn(async move {
-                if let Err(e) = handle_event(queue, event, http).await {
+                let res =
+                    handle_event(queue, event, http, application, commands, state, platform).await;
+                if let Err(e) = res {
                     error!("error during event handling: {}", e);
                 }
             });
@@ -54,9 +130,33 @@ pub async fn start(config: &Discord, queue: Queue, mut shutdown: Shutdown) -> Re
     Ok(())
 }
 
-async fn handle_event(queue: Queue, event: Event, http: Client) -> Result<()> {
+async fn handle_event(
+    queue: Queue,
+    event: Event,
+    http: Client,
+    application: ApplicationId,
+    commands: CommandList,
+    state: AsyncState,
+    platform: String,
+) -> Result<()> {
     match event {
-        Event::MessageCreate(msg) => handle_message(queue, msg.0, http).await?,
+        Event::MessageCreate(msg) => handle_message(queue, msg.0, http, state, platform).await?,
+        Event::ShardPayload(payload) => {
+            if let Some(interaction) = interaction::parse(&payload.bytes)? {
+                handle_command(
+                    queue,
+                    interaction,
+                    http,
+                    application,
+                    commands,
+                    state,
+                    platform,
+                )
+                .await?;
+            }
+        }
+        Event::ReactionAdd(reaction) => roles::react(&http, &state, reaction.0, true).await?,
+        Event::ReactionRemove(reaction) => roles::react(&http, &state, reaction.0, false).await?,
         Event::Ready(_) => info!("discord connection ready, listening for events"),
         _ => {}
     }
@@ -64,6 +164,18 @@ async fn handle_event(queue: Queue, event: Event, http: Client) -> Result<()> {
     Ok(())
 }
 
+async fn handle_outgoing(message: Outgoing, http: &Client) -> Result<()> {
+    let channel = message
+        .channel
+        .ok_or_else(|| anyhow!("outgoing Discord messages need a channel ID"))?;
+
+    http.create_message(ChannelId(channel))
+        .content(message.content)?
+        .await?;
+
+    Ok(())
+}
+
 /// List of admins that are allowed to customize the bot. Currently static and will be added to the
 /// settings in the future.
 const ADMINS: &[(&str, &str)] = &[
@@ -73,25 +185,114 @@ const ADMINS: &[(&str, &str)] = &[
     ("TrolledWoods", "2954"),
 ];
 
-async fn handle_message(queue: Queue, msg: ChannelMessage, http: Client) -> Result<()> {
+async fn handle_message(
+    queue: Queue,
+    msg: ChannelMessage,
+    http: Client,
+    state: AsyncState,
+    platform: String,
+) -> Result<()> {
     if msg.author.bot {
         // Ignore bots and our own messages.
         return Ok(());
     }
 
+    let is_admin = ADMINS.contains(&(&msg.author.name, &msg.author.discriminator));
     let message = Message {
         source: Source::Discord,
+        platform,
         content: msg.content.clone(),
-        admin: msg.guild_id.is_none()
-            && ADMINS.contains(&(&msg.author.name, &msg.author.discriminator)),
+        admin: msg.guild_id.is_none() && is_admin,
+        // Admins act as moderators in the server channels.
+        moderator: msg.guild_id.is_some() && is_admin,
+        author: msg.author.name.clone(),
+    };
+
+    respond(queue, message, Target::Message(Box::new(msg)), http, state).await
+}
+
+/// Handle a used slash command, which is turned into a chat message and answered just like one.
+/// Admin commands work anywhere, as their answer is only visible to the admin.
+async fn handle_command(
+    queue: Queue,
+    command: Interaction,
+    http: Client,
+    application: ApplicationId,
+    commands: CommandList,
+    state: AsyncState,
+    platform: String,
+) -> Result<()> {
+    let data = command
+        .data
+        .as_ref()
+        .ok_or_else(|| anyhow!("slash command without data"))?;
+    let info = slash::find(&commands.borrow(), &data.name).cloned();
+    let info = info.ok_or_else(|| anyhow!("unknown slash command `{}`", data.name))?;
+
+    interaction::defer(&http, &command, info.admin).await?;
+
+    let Interaction {
+        token,
+        guild_id,
+        member,
+        user,
+        data,
+        ..
+    } = command;
+    let author = member
+        .and_then(|member| member.user)
+        .or(user)
+        .ok_or_else(|| anyhow!("slash command `{}` without a user", info.name))?;
+    let options = data.map(|data| data.options).unwrap_or_default();
+
+    let target = Target::Interaction { application, token };
+    let is_admin = ADMINS.contains(&(&author.name, &author.discriminator));
+
+    if info.admin && !is_admin {
+        return target
+            .content(
+                &http,
+                format!("{} only admins can do that", emojis::COLLISION),
+            )
+            .await;
+    }
+
+    let message = Message {
+        source: Source::Discord,
+        platform,
+        content: slash::content(&info, &options),
+        admin: info.admin,
+        moderator: !info.admin && guild_id.is_some() && is_admin,
+        author: author.name,
     };
+
+    respond(queue, message, target, http, state).await
+}
+
+async fn respond(
+    queue: Queue,
+    message: Message,
+    target: Target,
+    http: Client,
+    state: AsyncState,
+) -> Result<()> {
     let (tx, rx) = oneshot::channel();
 
     if queue.send((message, tx)).await.is_ok() {
         if let Ok(resp) = rx.await {
             match resp {
-                Response::User(user_resp) => handle_user_message(user_resp, msg, http).await?,
-                Response::Admin(admin_resp) => handle_admin_message(admin_resp, msg, http).await?,
+                Response::User(user_resp) => handle_user_message(user_resp, target, http).await?,
+                Response::Admin(AdminResponse::Roles(Ok(None))) => {
+                    // Changed roles show up in the role message right away.
+                    let res = roles::sync(&http, &state).await.map(|()| None);
+                    handle_admin_message(AdminResponse::Roles(res), target, http).await?;
+                }
+                Response::Admin(admin_resp) => {
+                    handle_admin_message(admin_resp, target, http).await?;
+                }
+                Response::Moderation(violation) => {
+                    handle_moderation(violation, target, http).await?;
+                }
             }
         }
     }
@@ -99,31 +300,184 @@ async fn handle_message(queue: Queue, msg: ChannelMessage, http: Client) -> Resu
     Ok(())
 }
 
-async fn handle_user_message(resp: UserResponse, msg: ChannelMessage, http: Client) -> Result<()> {
+/// Act on a message that broke a moderation rule. Slash commands can't be deleted, so their
+/// answer is just left empty.
+async fn handle_moderation(violation: Violation, target: Target, http: Client) -> Result<()> {
+    let msg = match target {
+        Target::Message(msg) => msg,
+        Target::Interaction { .. } => return target.nothing(&http).await,
+    };
+
+    // Every action is taken on its own, so a failed deletion still lets the warning through.
+    if violation.has(ModerationAction::Delete) {
+        if let Err(e) = http.delete_message(msg.channel_id, msg.id).await {
+            error!("failed deleting discord message: {}", e);
+        }
+    }
+
+    if violation.has(ModerationAction::Timeout) {
+        if let Some(guild) = msg.guild_id {
+            if let Err(e) = timeout_member(&http, guild, msg.author.id, violation.timeout).await {
+                error!("failed timing out discord member: {}", e);
+            }
+        }
+    }
+
+    if violation.has(ModerationAction::Warn) {
+        let user = format!("<@{}>", msg.author.id);
+        http.create_message(msg.channel_id)
+            .content(violation.warning(&user))?
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Keep a server member from chatting for the given seconds, which the Discord library doesn't
+/// support yet. Longer timeouts than Discord allows are shortened to the maximum.
+async fn timeout_member(http: &Client, guild: GuildId, user: UserId, seconds: u32) -> Result<()> {
+    #[derive(Serialize)]
+    struct Timeout {
+        communication_disabled_until: String,
+    }
+
+    let until = Utc::now() + Duration::seconds(seconds.min(MAX_TIMEOUT).into());
+    interaction::send(
+        http,
+        Method::Patch,
+        Path::GuildsIdMembersId(guild.0),
+        format!("guilds/{}/members/{}", guild, user),
+        &Timeout {
+            communication_disabled_until: until.to_rfc3339_opts(SecondsFormat::Secs, true),
+        },
+    )
+    .await
+}
+
+async fn handle_user_message(resp: UserResponse, target: Target, http: Client) -> Result<()> {
     match resp {
-        UserResponse::Commands(res) => user::commands(msg, http, res).await,
-        UserResponse::Links(links) => user::links(msg, http, links).await,
+        UserResponse::Commands { names, page } => user::commands(target, http, names, page).await,
+        UserResponse::Links(links) => user::links(target, http, links).await,
         UserResponse::Schedule {
             start,
             finish,
             off_days,
-        } => user::schedule(msg, http, start, finish, off_days).await,
-        UserResponse::Custom(content) => user::custom(msg, http, content).await,
-        UserResponse::Unknown => Ok(()),
-        UserResponse::WrongArgs => Ok(()),
+            exceptions,
+        } => user::schedule(target, http, start, finish, off_days, exceptions).await,
+        UserResponse::Custom(content) => user::custom(target, http, content).await,
+        UserResponse::Embed(embed) => user::embed(target, http, embed).await,
+        UserResponse::Quote(quote) => user::quote(target, http, quote).await,
+        UserResponse::Crate(lookup) => user::crate_info(target, http, lookup).await,
+        UserResponse::Play(res) => user::play(target, http, res).await,
+        UserResponse::Unknown => target.nothing(&http).await,
+        UserResponse::WrongArgs(error) => user::wrong_args(target, http, error).await,
     }
 }
 
-async fn handle_admin_message(
-    resp: AdminResponse,
-    msg: ChannelMessage,
-    http: Client,
-) -> Result<()> {
+async fn handle_admin_message(resp: AdminResponse, target: Target, http: Client) -> Result<()> {
     match resp {
-        AdminResponse::Help => admin::help(msg, http).await,
-        AdminResponse::Schedule(res) => admin::schedule(msg, http, res).await,
-        AdminResponse::OffDays(res) => admin::off_days(msg, http, res).await,
-        AdminResponse::CustomCommands(res) => admin::custom_commands(msg, http, res).await,
-        AdminResponse::Unknown => Ok(()),
+        AdminResponse::Help => admin::help(target, http).await,
+        AdminResponse::Schedule(res) => admin::schedule(target, http, res).await,
+        AdminResponse::OffDays(res) => admin::off_days(target, http, res).await,
+        AdminResponse::CustomCommands(res) => admin::custom_commands(target, http, res).await,
+        AdminResponse::Timers(res) => admin::timers(target, http, res).await,
+        AdminResponse::State(res) => admin::state(target, http, res).await,
+        AdminResponse::Quotes(res) => admin::quotes(target, http, res).await,
+        AdminResponse::Roles(res) => admin::roles(target, http, res).await,
+        AdminResponse::Unknown => target.nothing(&http).await,
+    }
+}
+
+/// Where the response to a command goes, either as reply to the chat message or as answer to the
+/// slash command.
+pub enum Target {
+    Message(Box<ChannelMessage>),
+    /// A slash command, that was already acknowledged and waits for its actual answer.
+    Interaction {
+        application: ApplicationId,
+        token: String,
+    },
+}
+
+impl Target {
+    async fn content(&self, http: &Client, content: String) -> Result<()> {
+        match self {
+            Self::Message(msg) => {
+                http.create_message(msg.channel_id)
+                    .reply(msg.id)
+                    .content(content)?
+                    .await?;
+            }
+            Self::Interaction { application, token } => {
+                interaction::answer(http, *application, token, Some(content), Vec::new()).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn embed(&self, http: &Client, content: Option<String>, embed: Embed) -> Result<()> {
+        match self {
+            Self::Message(msg) => {
+                let create = http.create_message(msg.channel_id).reply(msg.id);
+                let create = match content {
+                    Some(content) => create.content(content)?,
+                    None => create,
+                };
+                create.embed(embed)?.await?;
+            }
+            Self::Interaction { application, token } => {
+                interaction::answer(http, *application, token, content, vec![embed]).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reply with content that might be longer than a single message. It's split into several
+    /// messages if needed, or attached as a file with the given name if it's too long for that.
+    /// Slash commands only get a single answer, so it's cut off for them instead.
+    async fn long(&self, http: &Client, content: String, file_name: &str) -> Result<()> {
+        let msg = match self {
+            Self::Message(msg) => msg,
+            Self::Interaction { .. } => {
+                return self
+                    .content(http, render::truncate(&content, DISCORD_LIMIT))
+                    .await;
+            }
+        };
+        let parts = render::split(&content, DISCORD_LIMIT);
+
+        if parts.len() > MAX_PARTS {
+            http.create_message(msg.channel_id)
+                .reply(msg.id)
+                .content("The response is too long for a message, so here it is as a file")?
+                .attachment(file_name, content.into_bytes())
+                .await?;
+            return Ok(());
+        }
+
+        for (i, part) in parts.into_iter().enumerate() {
+            let create = http.create_message(msg.channel_id);
+            let create = if i == 0 { create.reply(msg.id) } else { create };
+            create.content(part)?.await?;
+        }
+
+        Ok(())
+    }
+
+    /// Finish a command that has no response. Chat messages are simply ignored, but slash
+    /// commands wait for an answer.
+    async fn nothing(&self, http: &Client) -> Result<()> {
+        match self {
+            Self::Message(_) => Ok(()),
+            Self::Interaction { .. } => {
+                let content = format!(
+                    "{} this command isn't available right now",
+                    emojis::COLLISION
+                );
+                self.content(http, content).await
+            }
+        }
     }
 }
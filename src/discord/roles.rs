@@ -0,0 +1,220 @@
+//! Self-assignable roles, that users get by reacting to the role message and lose again by
+//! removing their reaction.
+
+use anyhow::Result;
+use log::info;
+use twilight_http::{request::channel::reaction::RequestReactionType, Client};
+use twilight_model::{
+    channel::{Reaction, ReactionType},
+    id::{ChannelId, EmojiId, MessageId, RoleId},
+};
+
+use crate::{
+    handler::AsyncState,
+    settings::{Change, ReactionRole, ReactionRoles},
+};
+
+/// Post the role message, or bring the existing one up to date with the current roles. The bot
+/// reacts with the emoji of every role, so users only have to click them.
+pub async fn sync(http: &Client, state: &AsyncState) -> Result<()> {
+    let roles = state.read().await.reaction_roles.clone();
+    let channel = match roles.channel {
+        Some(channel) => ChannelId(channel),
+        None => return Ok(()),
+    };
+    let content = content(&roles);
+
+    let existing = match roles.message {
+        Some(id) => http.message(channel, MessageId(id)).await?,
+        None => None,
+    };
+
+    let message = if let Some(message) = existing {
+        if message.content != content {
+            http.update_message(channel, message.id)
+                .content(Some(content))?
+                .await?;
+        }
+
+        // Reactions of roles that were removed would otherwise stay around forever.
+        for reaction in message.reactions.iter().filter(|reaction| reaction.me) {
+            if !roles
+                .roles
+                .iter()
+                .any(|role| matches(role, &reaction.emoji))
+            {
+                let emoji = request_emoji(&reaction.emoji);
+                http.delete_current_user_reaction(channel, message.id, emoji)
+                    .await?;
+            }
+        }
+
+        message.id
+    } else {
+        let message = http.create_message(channel).content(content)?.await?;
+        info!("posted role message in channel {}", channel);
+
+        let mut state = state.write().await;
+        state.reaction_roles.message = Some(message.id.0);
+        state.persist(Change::ReactionRoles)?;
+
+        message.id
+    };
+
+    for role in &roles.roles {
+        http.create_reaction(channel, message, role_emoji(role))
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Grant or remove the role that belongs to a reaction, if it was made on the role message.
+pub async fn react(
+    http: &Client,
+    state: &AsyncState,
+    reaction: Reaction,
+    added: bool,
+) -> Result<()> {
+    let guild = match reaction.guild_id {
+        Some(guild) => guild,
+        None => return Ok(()),
+    };
+    // Ignore bots, including our own reactions on the role message.
+    if reaction
+        .member
+        .as_ref()
+        .map_or(false, |member| member.user.bot)
+    {
+        return Ok(());
+    }
+
+    let role = {
+        let state = state.read().await;
+        let roles = &state.reaction_roles;
+        if roles.message != Some(reaction.message_id.0) {
+            return Ok(());
+        }
+
+        match roles
+            .roles
+            .iter()
+            .find(|role| matches(role, &reaction.emoji))
+        {
+            Some(role) => RoleId(role.role),
+            None => return Ok(()),
+        }
+    };
+
+    if added {
+        http.add_guild_member_role(guild, reaction.user_id, role)
+            .await?;
+        info!("gave role {} to user {}", role, reaction.user_id);
+    } else {
+        http.remove_guild_member_role(guild, reaction.user_id, role)
+            .await?;
+        info!("took role {} from user {}", role, reaction.user_id);
+    }
+
+    Ok(())
+}
+
+fn content(roles: &ReactionRoles) -> String {
+    let mut content = "React to this message to get a role, or remove your reaction to lose it \
+                       again:\n"
+        .to_owned();
+
+    for role in &roles.roles {
+        content.push('\n');
+        content.push_str(&role.emoji);
+        content.push(' ');
+        content.push_str(&role.description);
+    }
+
+    content
+}
+
+fn matches(role: &ReactionRole, emoji: &ReactionType) -> bool {
+    match (role.custom_emoji(), emoji) {
+        (Some((_, id)), ReactionType::Custom { id: emoji_id, .. }) => id == emoji_id.0,
+        (None, ReactionType::Unicode { name }) => role.emoji == *name,
+        _ => false,
+    }
+}
+
+fn role_emoji(role: &ReactionRole) -> RequestReactionType {
+    match role.custom_emoji() {
+        Some((name, id)) => RequestReactionType::Custom {
+            id: EmojiId(id),
+            name: Some(name.to_owned()),
+        },
+        None => RequestReactionType::Unicode {
+            name: role.emoji.clone(),
+        },
+    }
+}
+
+fn request_emoji(emoji: &ReactionType) -> RequestReactionType {
+    match emoji {
+        ReactionType::Custom { id, name, .. } => RequestReactionType::Custom {
+            id: *id,
+            name: name.clone(),
+        },
+        ReactionType::Unicode { name } => RequestReactionType::Unicode { name: name.clone() },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn role_message() {
+        let roles = ReactionRoles {
+            channel: Some(1),
+            message: None,
+            roles: vec![
+                ReactionRole {
+                    emoji: "🦀".to_owned(),
+                    role: 2,
+                    description: "Rustacean".to_owned(),
+                },
+                ReactionRole {
+                    emoji: "<:bell:3>".to_owned(),
+                    role: 4,
+                    description: "Stream notifications".to_owned(),
+                },
+            ],
+        };
+
+        assert_eq!(
+            "React to this message to get a role, or remove your reaction to lose it again:\n\n\
+             🦀 Rustacean\n\
+             <:bell:3> Stream notifications",
+            content(&roles)
+        );
+
+        assert!(matches(
+            &roles.roles[0],
+            &ReactionType::Unicode {
+                name: "🦀".to_owned()
+            }
+        ));
+        assert!(matches(
+            &roles.roles[1],
+            &ReactionType::Custom {
+                animated: false,
+                id: EmojiId(3),
+                name: None,
+            }
+        ));
+        assert!(!matches(
+            &roles.roles[1],
+            &ReactionType::Unicode {
+                name: "🦀".to_owned()
+            }
+        ));
+    }
+}
@@ -0,0 +1,307 @@
+//! Slash commands, which are registered with Discord from the list of commands and turned back
+//! into chat messages when they're used.
+
+use log::{info, warn};
+use serde_json::Value;
+use twilight_http::Client;
+use twilight_model::id::ApplicationId;
+
+use super::interaction::{
+    self, Command, CommandDataOption, CommandOption, CommandOptionChoice, OptionType,
+};
+use crate::{
+    commands::Type,
+    render,
+    settings::{Argument, Arity},
+    CommandInfo, CommandList,
+};
+
+/// Maximum length of command and option names.
+const MAX_NAME: usize = 32;
+/// Maximum length of command and option descriptions.
+const MAX_DESCRIPTION: usize = 100;
+/// Maximum number of choices of a single option.
+const MAX_CHOICES: usize = 25;
+
+/// Register the current commands and update them whenever the list changes.
+pub async fn sync(
+    http: Client,
+    application: ApplicationId,
+    guild: Option<u64>,
+    mut commands: CommandList,
+) {
+    loop {
+        let list = commands.borrow().clone();
+        let definitions = definitions(&list);
+        match interaction::set_commands(&http, application, guild, &definitions).await {
+            Ok(()) => info!("registered {} slash commands", list.len()),
+            Err(e) => warn!("failed registering slash commands: {}", e),
+        }
+
+        if commands.changed().await.is_err() {
+            break;
+        }
+    }
+}
+
+/// Turn the list of commands into slash command definitions. Commands with names that Discord
+/// doesn't accept are left out, but can still be used in chat.
+fn definitions(list: &[CommandInfo]) -> Vec<Command> {
+    list.iter()
+        .filter(|info| {
+            let valid = is_valid_name(&info.name);
+            if !valid {
+                info!("command `{}` can't be a slash command", info.name);
+            }
+            valid
+        })
+        .map(|info| Command {
+            name: info.name.clone(),
+            description: description(&info.description, &info.name),
+            options: option_names(&info.args)
+                .into_iter()
+                .zip(&info.args)
+                .map(|(name, arg)| option(name, arg))
+                .collect(),
+        })
+        .collect()
+}
+
+/// Discord only allows lowercase letters, numbers, `-` and `_` in names.
+fn is_valid_name(name: &str) -> bool {
+    (1..=MAX_NAME).contains(&name.len())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+}
+
+/// Find the slash command for the name of the command that was used.
+pub fn find<'a>(list: &'a [CommandInfo], name: &str) -> Option<&'a CommandInfo> {
+    list.iter()
+        .find(|info| info.name == name && is_valid_name(&info.name))
+}
+
+fn description(description: &str, name: &str) -> String {
+    let description = description.trim();
+    if description.is_empty() {
+        format!("!{}", name)
+    } else {
+        render::truncate(description, MAX_DESCRIPTION)
+    }
+}
+
+/// Names of the options for the arguments, taken from the argument names or their types and made
+/// valid and unique.
+fn option_names(args: &[Argument]) -> Vec<String> {
+    let mut names = Vec::<String>::with_capacity(args.len());
+
+    for arg in args {
+        let name = arg
+            .name
+            .clone()
+            .unwrap_or_else(|| type_name(&arg.ty).to_owned())
+            .to_lowercase()
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .take(MAX_NAME - 3)
+            .collect::<String>();
+
+        let mut unique = name.clone();
+        let mut counter = 1;
+        while names.contains(&unique) {
+            counter += 1;
+            unique = format!("{}_{}", name, counter);
+        }
+
+        names.push(unique);
+    }
+
+    names
+}
+
+const fn type_name(ty: &Type) -> &'static str {
+    match ty {
+        Type::String(_) => "string",
+        Type::Int { .. } => "int",
+        Type::Choice(_) => "choice",
+        Type::User => "user",
+        Type::Duration => "duration",
+        Type::Url(_) => "url",
+    }
+}
+
+fn option(name: String, arg: &Argument) -> CommandOption {
+    let (kind, choices) = match &arg.ty {
+        Type::Int { .. } => (OptionType::Integer, Vec::new()),
+        Type::Choice(choices) => (
+            OptionType::String,
+            choices
+                .iter()
+                .take(MAX_CHOICES)
+                .map(|choice| CommandOptionChoice {
+                    name: choice.clone(),
+                    value: choice.clone(),
+                })
+                .collect(),
+        ),
+        Type::User => (OptionType::User, Vec::new()),
+        Type::String(_) | Type::Duration | Type::Url(_) => (OptionType::String, Vec::new()),
+    };
+
+    CommandOption {
+        kind,
+        name,
+        description: render::truncate(&describe(arg), MAX_DESCRIPTION),
+        required: arg.arity == Arity::Required,
+        choices,
+    }
+}
+
+fn describe(arg: &Argument) -> String {
+    let mut description = match &arg.ty {
+        Type::String(_) if arg.arity == Arity::Rest => "Any text".to_owned(),
+        Type::String(1) => "A single word".to_owned(),
+        Type::String(count) => format!("{} words", count),
+        Type::Int {
+            min: Some(min),
+            max: Some(max),
+        } => format!("A number from {} to {}", min, max),
+        Type::Int {
+            min: Some(min),
+            max: None,
+        } => format!("A number of at least {}", min),
+        Type::Int {
+            min: None,
+            max: Some(max),
+        } => format!("A number of at most {}", max),
+        Type::Int { .. } => "A number".to_owned(),
+        Type::Choice(_) => "One of the choices".to_owned(),
+        Type::User => "A user".to_owned(),
+        Type::Duration => "A duration like 1h30m".to_owned(),
+        Type::Url(Some(_)) => "A name".to_owned(),
+        Type::Url(None) => "A link".to_owned(),
+    };
+
+    if let Arity::Default(value) = &arg.arity {
+        description.push_str(&format!(" (default: {})", value));
+    }
+
+    description
+}
+
+/// Turn the options of a used slash command back into a chat message, so it's handled exactly
+/// like the command written in chat. The `admin` command becomes the admin command it contains,
+/// or the admin help if it's empty.
+///
+/// Arguments are positional in chat, so a missing optional argument ends the message, unless it
+/// has a default value that can be filled in instead.
+pub fn content(info: &CommandInfo, options: &[CommandDataOption]) -> String {
+    let mut words = Vec::with_capacity(info.args.len() + 1);
+    if !info.admin {
+        words.push(format!("!{}", info.name));
+    }
+
+    for (name, arg) in option_names(&info.args).iter().zip(&info.args) {
+        let value = options
+            .iter()
+            .find(|option| option.name == *name)
+            .and_then(|option| match &option.value {
+                Value::String(value) => Some(value.clone()),
+                Value::Number(value) => Some(value.to_string()),
+                Value::Bool(value) => Some(value.to_string()),
+                _ => None,
+            });
+
+        match (value, &arg.arity) {
+            (Some(value), _) if arg.ty == Type::User => words.push(format!("<@{}>", value)),
+            (Some(value), _) => words.push(value),
+            (None, Arity::Default(value)) => words.push(value.clone()),
+            (None, _) => break,
+        }
+    }
+
+    if info.admin && words.is_empty() {
+        "!help".to_owned()
+    } else if info.admin {
+        format!("!{}", words.join(" "))
+    } else {
+        words.join(" ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn info(name: &str, args: &[&str]) -> CommandInfo {
+        CommandInfo {
+            name: name.to_owned(),
+            description: String::new(),
+            args: args.iter().map(|arg| arg.parse().unwrap()).collect(),
+            admin: false,
+        }
+    }
+
+    #[test]
+    fn names() {
+        assert!(is_valid_name("crate"));
+        assert!(is_valid_name("off_days-2"));
+        assert!(!is_valid_name("Crate"));
+        assert!(!is_valid_name("café"));
+        assert!(!is_valid_name(""));
+
+        let info = info("so", &["user", "Full_Name:string", "user", "int?"]);
+        assert_eq!(
+            vec!["user", "full_name", "user_2", "int"],
+            option_names(&info.args)
+        );
+
+        let list = definitions(&[info, self::info("Bad", &[])]);
+        assert_eq!(1, list.len());
+        assert_eq!("!so", list[0].description);
+    }
+
+    #[test]
+    fn build_content() {
+        let so = info("so", &["user", "times:int=1", "text:string..."]);
+        let option = |name: &str, value: Value| CommandDataOption {
+            name: name.to_owned(),
+            value,
+        };
+        let string = |name: &str, value: &str| option(name, Value::from(value));
+
+        assert_eq!(
+            "!so <@123> 3 hello there",
+            content(
+                &so,
+                &[
+                    string("text", "hello there"),
+                    option("times", Value::from(3)),
+                    string("user", "123"),
+                ]
+            )
+        );
+        assert_eq!(
+            "!so <@123> 1 hi",
+            content(&so, &[string("user", "123"), string("text", "hi")])
+        );
+
+        let admin = CommandInfo {
+            admin: true,
+            ..info("admin", &["command:string..."])
+        };
+        assert_eq!(
+            "!schedule skip 2021-06-01",
+            content(&admin, &[string("command", "schedule skip 2021-06-01")])
+        );
+        assert_eq!("!help", content(&admin, &[]));
+    }
+}
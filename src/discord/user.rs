@@ -1,82 +1,92 @@
 use anyhow::Result;
 use indoc::indoc;
 use log::error;
-use twilight_embed_builder::{EmbedBuilder, EmbedFieldBuilder};
+use twilight_embed_builder::{EmbedBuilder, EmbedFieldBuilder, EmbedFooterBuilder, ImageSource};
 use twilight_http::Client;
-use twilight_model::channel::Message as ChannelMessage;
 
-use crate::settings::Links;
+use crate::{
+    commands::{crates::CrateLookup, playground::Evaluation},
+    emojis,
+    render::{self, DISCORD_LIMIT},
+    settings::{Embed, Links, Quote},
+    ArgsError,
+};
+
+use super::Target;
+
+/// Introduction to the list of commands, describing all built-in ones.
+const COMMANDS_HEADER: &str = indoc! {"
+    Available commands:
+    `!help` (or `!bot`) gives a short info about this bot.
+    `!lark` tells **togglebit** that he's a lark.
+    `!links` gives you a list of links to sites where **togglebit** is present.
+    `!schedule` tells you the Twitch streaming schedule of **togglebit**.
+    `!crate <name>` shows the latest version and docs of a crate.
+    `!ban` refuse anything with the power of Gandalf.
+    `!quote` shows a random quote, one by its number or one containing a keyword.
+    `!play <code>` runs Rust code, `!eval <expr>` shows the value of an expression.
+
+    Further custom commands:
+"};
 
 /// Gandalf's famous "You shall not pass!" scene.
 
-pub async fn commands(msg: ChannelMessage, http: Client, res: Result<Vec<String>>) -> Result<()> {
+pub async fn commands(
+    target: Target,
+    http: Client,
+    res: Result<Vec<String>>,
+    page: usize,
+) -> Result<()> {
     let message = match res {
-        Ok(names) => names.into_iter().enumerate().fold(
-            String::from(indoc! {"
-                    Available commands:
-                    `!help` (or `!bot`) gives a short info about this bot.
-                    `!lark` tells **togglebit** that he's a lark.
-                    `!links` gives you a list of links to sites where **togglebit** is present.
-                    `!schedule` tells you the Twitch streaming schedule of **togglebit**.
-                    `!crate` get the link for any existing crate.
-                    `!ban` refuse anything with the power of Gandalf.
-
-                    Further custom commands:
-                "}),
-            |mut list, (i, name)| {
-                if i > 0 {
-                    list.push_str(", ");
-                }
-                list.push_str("`!");
-                list.push_str(&name);
-                list.push('`');
-                list
-            },
-        ),
+        Ok(names) => {
+            let names = names
+                .iter()
+                .map(|name| format!("`!{}`", name))
+                .collect::<Vec<_>>();
+            // Leave some room for the page number below the list.
+            let limit = DISCORD_LIMIT - COMMANDS_HEADER.chars().count() - 100;
+
+            match render::page(&names, ", ", limit, page) {
+                (Some(list), 1) => format!("{}{}", COMMANDS_HEADER, list),
+                (Some(list), total) => format!(
+                    "{}{}\n\nPage {} of {}, use `!commands <page>` to see the others.",
+                    COMMANDS_HEADER, list, page, total
+                ),
+                (None, total) => format!("The last page of commands is {}", total),
+            }
+        }
         Err(e) => {
             error!("failed listing commands: {}", e);
             "Sorry, something went wrong fetching the list of commands".to_owned()
         }
     };
 
-    http.create_message(msg.channel_id)
-        .reply(msg.id)
-        .content(message)?
-        .await?;
-
-    Ok(())
+    target.content(&http, message).await
 }
 
-pub async fn links(msg: ChannelMessage, http: Client, links: Links) -> Result<()> {
-    http.create_message(msg.channel_id)
-        .reply(msg.id)
-        .content(
-            links
-                .into_iter()
-                .enumerate()
-                .fold(String::new(), |mut list, (i, (name, url))| {
-                    if i > 0 {
-                        list.push('\n');
-                    }
-
-                    list.push_str(&name);
-                    list.push_str(": <");
-                    list.push_str(&url);
-                    list.push('>');
-                    list
-                }),
-        )?
-        .await?;
-
-    Ok(())
+pub async fn links(target: Target, http: Client, links: Links) -> Result<()> {
+    let mut content = String::new();
+    for (i, (name, url)) in links.into_iter().enumerate() {
+        if i > 0 {
+            content.push('\n');
+        }
+
+        content.push_str(&name);
+        content.push_str(": <");
+        content.push_str(&url);
+        content.push('>');
+    }
+
+    target.content(&http, content).await
 }
 
 pub async fn schedule(
-    msg: ChannelMessage,
+    target: Target,
     http: Client,
     start: String,
     finish: String,
     off_days: Vec<String>,
+    exceptions: Vec<String>,
 ) -> Result<()> {
     let last_off_day = off_days.len() - 1;
     let days = format!(
@@ -102,26 +112,148 @@ pub async fn schedule(
         start, finish
     );
 
-    http.create_message(msg.channel_id)
-        .reply(msg.id)
-        .content("Here is togglebit's stream schedule:")?
-        .embed(
-            EmbedBuilder::new()
-                .field(EmbedFieldBuilder::new("Days", days))
-                .field(EmbedFieldBuilder::new("Time", time))
-                .field(EmbedFieldBuilder::new("Timezone", "CET"))
-                .build()?,
-        )?
-        .await?;
-
-    Ok(())
+    let mut embed = EmbedBuilder::new()
+        .field(EmbedFieldBuilder::new("Days", days))
+        .field(EmbedFieldBuilder::new("Time", time))
+        .field(EmbedFieldBuilder::new("Timezone", "CET"));
+
+    if !exceptions.is_empty() {
+        embed = embed.field(EmbedFieldBuilder::new("Exceptions", exceptions.join("\n")));
+    }
+
+    let content = "Here is togglebit's stream schedule:".to_owned();
+    target.embed(&http, Some(content), embed.build()?).await
 }
 
-pub async fn custom(msg: ChannelMessage, http: Client, content: String) -> Result<()> {
-    http.create_message(msg.channel_id)
-        .reply(msg.id)
-        .content(content)?
-        .await?;
+pub async fn custom(target: Target, http: Client, content: String) -> Result<()> {
+    target.content(&http, content).await
+}
+
+pub async fn embed(target: Target, http: Client, embed: Embed) -> Result<()> {
+    let mut builder = EmbedBuilder::new();
+
+    if let Some(title) = embed.title {
+        builder = builder.title(title);
+    }
+    if let Some(description) = embed.description {
+        builder = builder.description(description);
+    }
+    if let Some(url) = embed.url {
+        builder = builder.url(url);
+    }
+    if let Some(color) = embed.color {
+        builder = builder.color(color.0);
+    }
+    if let Some(thumbnail) = embed.thumbnail {
+        builder = builder.thumbnail(ImageSource::url(thumbnail)?);
+    }
+    if let Some(footer) = embed.footer {
+        builder = builder.footer(EmbedFooterBuilder::new(footer));
+    }
+    for field in embed.fields {
+        let field_builder = EmbedFieldBuilder::new(field.name, field.value);
+        builder = builder.field(if field.inline {
+            field_builder.inline()
+        } else {
+            field_builder
+        });
+    }
+
+    target.embed(&http, None, builder.build()?).await
+}
+
+pub async fn wrong_args(target: Target, http: Client, error: ArgsError) -> Result<()> {
+    let content = match error.message {
+        Some(message) => message,
+        None => format!(
+            "{} {}, usage: `{}`",
+            emojis::COLLISION,
+            error.reason,
+            error.usage
+        ),
+    };
+
+    target.content(&http, content).await
+}
+
+pub async fn quote(target: Target, http: Client, quote: Option<Quote>) -> Result<()> {
+    let quote = if let Some(quote) = quote {
+        quote
+    } else {
+        return target
+            .content(&http, "No matching quote found".to_owned())
+            .await;
+    };
+
+    let embed = EmbedBuilder::new()
+        .title(format!("Quote #{}", quote.number))
+        .description(quote.text)
+        .field(EmbedFieldBuilder::new("Added by", quote.author).inline())
+        .field(EmbedFieldBuilder::new("Date", quote.date.to_string()).inline())
+        .field(EmbedFieldBuilder::new("Platform", quote.source.as_ref()).inline());
+
+    target.embed(&http, None, embed.build()?).await
+}
+
+pub async fn crate_info(target: Target, http: Client, lookup: Result<CrateLookup>) -> Result<()> {
+    let info = match lookup {
+        Ok(CrateLookup::Found(info)) => info,
+        Ok(CrateLookup::NotFound { name, suggestions }) => {
+            let mut content = format!("No crate named `{}` found", name);
+            if !suggestions.is_empty() {
+                content.push_str(", did you mean `");
+                content.push_str(&suggestions.join("`, `"));
+                content.push_str("`?");
+            }
+
+            return target.content(&http, content).await;
+        }
+        Err(e) => {
+            error!("failed looking up crate: {}", e);
+            return target
+                .content(
+                    &http,
+                    "Sorry, something went wrong looking up the crate".to_owned(),
+                )
+                .await;
+        }
+    };
+
+    let mut embed = EmbedBuilder::new()
+        .title(format!("{} {}", info.name, info.version))
+        .url(info.docs.clone());
+    if let Some(description) = info.description.filter(|d| !d.is_empty()) {
+        embed = embed.description(description);
+    }
+    let embed = embed
+        .field(EmbedFieldBuilder::new("Version", info.version).inline())
+        .field(EmbedFieldBuilder::new("Downloads", info.downloads.to_string()).inline())
+        .field(EmbedFieldBuilder::new("Docs", info.docs).inline());
+
+    target.embed(&http, None, embed.build()?).await
+}
+
+pub async fn play(target: Target, http: Client, res: Result<Evaluation>) -> Result<()> {
+    let content = match res {
+        Ok(eval) => {
+            let output = if eval.output.is_empty() {
+                "no output".to_owned()
+            } else {
+                // Keep the output from closing the code block early.
+                eval.output.replace("```", "`\u{200b}``")
+            };
+
+            if eval.success {
+                format!("```\n{}\n```", output)
+            } else {
+                format!("{} failed:\n```\n{}\n```", emojis::COLLISION, output)
+            }
+        }
+        Err(e) => {
+            error!("failed running code: {}", e);
+            "Sorry, something went wrong running the code".to_owned()
+        }
+    };
 
-    Ok(())
+    target.content(&http, content).await
 }
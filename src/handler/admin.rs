@@ -1,11 +1,15 @@
-use std::str::FromStr;
+use std::{ffi::OsStr, fs, num::NonZeroU32, path::Path, str::FromStr};
 
 use anyhow::{anyhow, bail, ensure, Result};
-use chrono::{NaiveTime, Weekday};
+use chrono::{NaiveDate, NaiveTime, Utc, Weekday};
 use log::info;
 
 use super::AsyncState;
-use crate::{settings, AdminResponse, Source};
+use crate::{
+    backup::{self, Format},
+    settings::{self, Argument, Change, Command, Config, DataDir, ExtraStream, ReactionRole},
+    AdminResponse, Source, StateReport,
+};
 
 pub fn help() -> AdminResponse {
     info!("admin: received `help` command");
@@ -63,7 +67,148 @@ async fn update_schedule(
         Field::Finish => state.schedule.finish = range,
     }
 
-    settings::save_state(&*state).await?;
+    state.persist(Change::Schedule)?;
+
+    Ok(())
+}
+
+pub async fn schedule_skip(state: AsyncState, date: &str) -> AdminResponse {
+    info!("admin: received `schedule skip` command");
+
+    let res = || async {
+        let date = parse_future_date(date)?;
+        update_exceptions(state, |exceptions| {
+            exceptions.skips.insert(date);
+            Ok(())
+        })
+        .await
+    };
+
+    AdminResponse::Schedule(res().await)
+}
+
+pub async fn schedule_extra(
+    state: AsyncState,
+    content: &str,
+    date: &str,
+    start: &str,
+    finish: &str,
+) -> AdminResponse {
+    info!("admin: received `schedule extra` command");
+
+    let title = Some(skip_words(content, 5).trim_matches('"'))
+        .filter(|title| !title.is_empty())
+        .map(ToOwned::to_owned);
+
+    let res = || async {
+        let date = parse_future_date(date)?;
+        let extra = ExtraStream {
+            start: parse_time(start)?,
+            finish: parse_time(finish)?,
+            title,
+        };
+        ensure!(
+            extra.start < extra.finish,
+            "the stream must start before it finishes",
+        );
+
+        update_exceptions(state, |exceptions| {
+            exceptions.extras.insert(date, extra);
+            Ok(())
+        })
+        .await
+    };
+
+    AdminResponse::Schedule(res().await)
+}
+
+pub async fn schedule_vacation(state: AsyncState, range: &str) -> AdminResponse {
+    info!("admin: received `schedule vacation` command");
+
+    let res = || async {
+        let (start, end) = range
+            .split_once("..")
+            .ok_or_else(|| anyhow!("vacations must be given as `<start>..<end>`"))?;
+        // An ongoing vacation can be extended, so only its end must lie in the future.
+        let (start, end) = (parse_date(start)?, parse_future_date(end)?);
+        ensure!(start <= end, "the vacation must start before it ends");
+
+        update_exceptions(state, |exceptions| {
+            exceptions.vacations.insert((start, end));
+            Ok(())
+        })
+        .await
+    };
+
+    AdminResponse::Schedule(res().await)
+}
+
+pub async fn schedule_remove(state: AsyncState, date: &str) -> AdminResponse {
+    info!("admin: received `schedule remove` command");
+
+    let res = || async {
+        let date = parse_date(date)?;
+        update_exceptions(state, |exceptions| {
+            ensure!(
+                exceptions.remove(date),
+                "there is no schedule exception for {}",
+                date,
+            );
+            Ok(())
+        })
+        .await
+    };
+
+    AdminResponse::Schedule(res().await)
+}
+
+/// Get the rest of a message after the given number of words, no matter how much whitespace
+/// separates them.
+fn skip_words(content: &str, words: usize) -> &str {
+    let mut rest = content.trim();
+    for _ in 0..words {
+        rest = rest
+            .find(char::is_whitespace)
+            .map_or("", |pos| rest[pos..].trim_start());
+    }
+
+    rest
+}
+
+/// Parse a date in ISO format.
+fn parse_date(date: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_e| anyhow!("invalid date `{}`, expected `YYYY-MM-DD`", date))
+}
+
+/// Parse a date in ISO format that must not lie in the past.
+fn parse_future_date(date: &str) -> Result<NaiveDate> {
+    let date = parse_date(date)?;
+    ensure!(
+        date >= settings::today(),
+        "the date {} is in the past",
+        date
+    );
+
+    Ok(date)
+}
+
+/// Parse a time in either 24-hour format like `14:00` or 12-hour format like `02:00pm`.
+fn parse_time(time: &str) -> Result<NaiveTime> {
+    NaiveTime::parse_from_str(time, "%H:%M")
+        .or_else(|_| NaiveTime::parse_from_str(time, "%I:%M%P"))
+        .map_err(|_e| anyhow!("invalid time `{}`, expected `HH:MM[am|pm]`", time))
+}
+
+async fn update_exceptions(
+    state: AsyncState,
+    update: impl FnOnce(&mut settings::ScheduleExceptions) -> Result<()>,
+) -> Result<()> {
+    let mut state = state.write().await;
+    state.schedule_exceptions.prune(settings::today());
+    update(&mut state.schedule_exceptions)?;
+
+    state.persist(Change::Schedule)?;
 
     Ok(())
 }
@@ -113,7 +258,7 @@ async fn update_off_days(state: AsyncState, action: Action, weekday: Weekday) ->
         }
     }
 
-    settings::save_state(&*state).await?;
+    state.persist(Change::Schedule)?;
 
     Ok(())
 }
@@ -123,21 +268,21 @@ pub async fn custom_commands_list(state: AsyncState) -> AdminResponse {
     AdminResponse::CustomCommands(list_commands(state).await.map(Some))
 }
 
-async fn list_commands(state: AsyncState) -> Result<Vec<(String, Source, String)>> {
-    Ok(state
+async fn list_commands(state: AsyncState) -> Result<Vec<(String, Command)>> {
+    let mut list = state
         .read()
         .await
         .custom_commands
         .iter()
-        .flat_map(|(name, sources)| {
-            sources
-                .iter()
-                .map(move |(source, content)| (name.clone(), *source, content.clone()))
-        })
-        .collect())
+        .map(|(name, command)| (name.clone(), command.clone()))
+        .collect::<Vec<_>>();
+    list.sort_by(|a, b| a.0.cmp(&b.0));
+
+    Ok(list)
 }
 
 pub async fn custom_commands(
+    config: &Config,
     state: AsyncState,
     content: &str,
     action: &str,
@@ -146,22 +291,92 @@ pub async fn custom_commands(
 ) -> AdminResponse {
     info!("admin: received `custom_commands` command");
 
-    let content = content
+    let rest = content
         .splitn(5, char::is_whitespace)
         .filter(|c| !c.is_empty())
-        .nth(4);
+        .nth(4)
+        .unwrap_or_default();
+
+    let res = || async {
+        let (options, content) = CommandOptions::parse(rest)?;
+        update_commands(
+            config,
+            state,
+            action.parse()?,
+            source.parse()?,
+            name,
+            options,
+            content,
+        )
+        .await
+    };
 
-    let res =
-        || async { update_commands(state, action.parse()?, source.parse()?, name, content).await };
+    AdminResponse::CustomCommands(res().await.map(|_| None))
+}
+
+/// Manage a custom command for all sources at once, with the syntax `<name> [options] [content]`
+/// as used by the chat commands for moderators.
+pub(super) async fn chat_command(
+    config: &Config,
+    state: AsyncState,
+    action: CommandAction,
+    name: &str,
+    rest: &str,
+) -> AdminResponse {
+    info!("admin: received chat command to manage custom commands");
+
+    let name = name.trim_start_matches('!').to_lowercase();
+
+    let res = || async {
+        let (options, content) = CommandOptions::parse(rest)?;
+        update_commands(
+            config,
+            state,
+            action,
+            CommandSource::All,
+            &name,
+            options,
+            content,
+        )
+        .await
+    };
 
     AdminResponse::CustomCommands(res().await.map(|_| None))
 }
 
-enum CommandSource {
+pub(super) enum CommandAction {
+    Add,
+    Edit,
+    Remove,
+}
+
+impl FromStr for CommandAction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "add" => Self::Add,
+            "edit" => Self::Edit,
+            "remove" => Self::Remove,
+            s => bail!("unknown action `{}`", s),
+        })
+    }
+}
+
+pub(super) enum CommandSource {
     Source(Source),
     All,
 }
 
+impl From<CommandSource> for Option<Source> {
+    fn from(source: CommandSource) -> Self {
+        match source {
+            CommandSource::Source(source) => Some(source),
+            CommandSource::All => None,
+        }
+    }
+}
+
 impl FromStr for CommandSource {
     type Err = anyhow::Error;
 
@@ -175,15 +390,110 @@ impl FromStr for CommandSource {
     }
 }
 
-const RESERVED_COMMANDS: &[&str] = &["help", "bot", "commands", "links", "schedule", "ban"];
+/// Optional settings for a custom command, given as `--name value` pairs in front of the content.
+/// Each setting replaces the current one when editing a command, and `none` (or `0` for the
+/// cooldown) removes it.
+#[derive(Default)]
+pub(super) struct CommandOptions {
+    args: Option<Vec<Argument>>,
+    cooldown: Cooldown,
+    aliases: Option<Vec<String>>,
+}
 
-async fn update_commands(
-    state: AsyncState,
-    action: Action,
-    source: CommandSource,
-    name: &str,
-    content: Option<&str>,
-) -> Result<()> {
+/// What to do with the cooldown of an edited command.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum Cooldown {
+    #[default]
+    Keep,
+    Clear,
+    Set(NonZeroU32),
+}
+
+impl CommandOptions {
+    /// Parse all leading options from the input and return them together with the remaining
+    /// content, if any.
+    pub(super) fn parse(input: &str) -> Result<(Self, Option<&str>)> {
+        let mut options = Self::default();
+        let mut rest = input.trim_start();
+
+        while let Some(option) = rest.strip_prefix("--") {
+            let (name, after) = next_token(option)?;
+            let (value, after) = next_token(after)?;
+            ensure!(!value.is_empty(), "missing value for option `--{}`", name);
+
+            match name {
+                "args" | "arg" => {
+                    let args = options.args.get_or_insert_with(Vec::new);
+                    if value != "none" {
+                        args.push(value.parse().map_err(|e: String| anyhow!(e))?);
+                    }
+                }
+                "cooldown" => {
+                    options.cooldown =
+                        NonZeroU32::new(value.parse()?).map_or(Cooldown::Clear, Cooldown::Set);
+                }
+                "aliases" | "alias" => {
+                    let aliases = options.aliases.get_or_insert_with(Vec::new);
+                    if value != "none" {
+                        aliases.extend(
+                            value
+                                .split(',')
+                                .filter(|alias| !alias.is_empty())
+                                .map(|alias| alias.trim_start_matches('!').to_owned()),
+                        );
+                    }
+                }
+                _ => bail!("unknown option `--{}`", name),
+            }
+
+            rest = after.trim_start();
+        }
+
+        if let Some(args) = &options.args {
+            settings::validate_args(args).map_err(|e| anyhow!(e))?;
+        }
+
+        let content = rest.trim_end();
+        Ok((options, (!content.is_empty()).then_some(content)))
+    }
+
+    fn apply(self, command: &mut Command) {
+        if let Some(args) = self.args {
+            command.args = (!args.is_empty()).then_some(args);
+        }
+        match self.cooldown {
+            Cooldown::Keep => {}
+            Cooldown::Clear => command.cooldown = None,
+            Cooldown::Set(cooldown) => command.cooldown = Some(cooldown),
+        }
+        if let Some(aliases) = self.aliases {
+            command.aliases = (!aliases.is_empty()).then_some(aliases);
+        }
+    }
+}
+
+/// Split off the next whitespace separated token, which may be wrapped in double quotes to
+/// include whitespace.
+fn next_token(input: &str) -> Result<(&str, &str)> {
+    let input = input.trim_start();
+
+    if let Some(quoted) = input.strip_prefix('"') {
+        let end = quoted
+            .find('"')
+            .ok_or_else(|| anyhow!("missing closing quote in `{}`", input))?;
+        Ok((&quoted[..end], &quoted[end + 1..]))
+    } else {
+        let end = input.find(char::is_whitespace).unwrap_or(input.len());
+        Ok(input.split_at(end))
+    }
+}
+
+const RESERVED_COMMANDS: &[&str] = &[
+    "help", "bot", "commands", "links", "schedule", "ban", "addcom", "editcom", "delcom", "quote",
+    "addquote", "play", "eval",
+];
+
+fn validate_name(config: &Config, name: &str) -> Result<()> {
     ensure!(
         !name.starts_with('!'),
         "command names must not start with an `!`",
@@ -202,35 +512,82 @@ async fn update_commands(
         "the command name `{}` is reserved",
         name,
     );
+    ensure!(
+        !config
+            .commands
+            .iter()
+            .any(|(key, item)| item.matches(key, name)),
+        "the command name `{}` is already used by a configured command",
+        name,
+    );
+
+    Ok(())
+}
+
+/// Update a runtime command. Configured commands always take precedence over runtime ones, so
+/// names and aliases that are already used in the config are rejected.
+pub(super) async fn update_commands(
+    config: &Config,
+    state: AsyncState,
+    action: CommandAction,
+    source: CommandSource,
+    name: &str,
+    options: CommandOptions,
+    content: Option<&str>,
+) -> Result<()> {
+    validate_name(config, name)?;
+    for alias in options.aliases.iter().flatten() {
+        validate_name(config, alias)?;
+    }
 
     let mut state = state.write().await;
+
+    if let Some(aliases) = &options.aliases {
+        let taken = state
+            .custom_commands
+            .iter()
+            .filter(|(other, _)| *other != name)
+            .find(|(other, command)| {
+                command.matches(other, name)
+                    || aliases.iter().any(|alias| command.matches(other, alias))
+            });
+        if let Some((other, _)) = taken {
+            bail!(
+                "the name or an alias is already used by the command `{}`",
+                other
+            );
+        }
+    }
+
     match action {
-        Action::Add => {
-            if let Some(content) = content {
-                match source {
-                    CommandSource::Source(source) => {
-                        state
-                            .custom_commands
-                            .entry(name.to_owned())
-                            .or_default()
-                            .insert(source, content.to_owned());
-                    }
-                    CommandSource::All => {
-                        let entry = state.custom_commands.entry(name.to_owned()).or_default();
+        CommandAction::Add => {
+            let content = content.ok_or_else(|| anyhow!("no content for the command provided"))?;
+            let command = state
+                .custom_commands
+                .entry(name.to_owned())
+                .or_insert_with(Command::empty);
+
+            command.set_content(source.into(), content);
+            options.apply(command);
+        }
+        CommandAction::Edit => {
+            let command = state
+                .custom_commands
+                .get_mut(name)
+                .ok_or_else(|| anyhow!("the command `{}` doesn't exist", name))?;
 
-                        for source in &[Source::Discord, Source::Twitch] {
-                            entry.insert(*source, content.to_owned());
-                        }
-                    }
-                }
-            } else {
-                bail!("no content for the command provided");
+            if let Some(content) = content {
+                command.set_content(source.into(), content);
             }
+            options.apply(command);
         }
-        Action::Remove => match source {
+        CommandAction::Remove => match source {
             CommandSource::Source(source) => {
-                if let Some(entry) = state.custom_commands.get_mut(name) {
-                    entry.remove(&source);
+                if let Some(command) = state.custom_commands.get_mut(name) {
+                    command.remove_source(source);
+                    if command.platforms.is_empty() {
+                        state.custom_commands.remove(name);
+                    }
                 }
             }
             CommandSource::All => {
@@ -239,7 +596,316 @@ async fn update_commands(
         },
     }
 
-    settings::save_state(&state).await?;
+    state.persist(Change::CustomCommand(name.into()))?;
 
     Ok(())
 }
+
+pub async fn timers_list(config: &Config, state: AsyncState) -> AdminResponse {
+    info!("admin: received `timers list` command");
+
+    let state = state.read().await;
+    let mut timers = config
+        .timers
+        .keys()
+        .map(|name| (name.clone(), state.paused_timers.contains(name)))
+        .collect::<Vec<_>>();
+    timers.sort();
+
+    AdminResponse::Timers(Ok(Some(timers)))
+}
+
+pub async fn timers(config: &Config, state: AsyncState, action: &str, name: &str) -> AdminResponse {
+    info!("admin: received `timers` command");
+
+    let res = || async { update_timers(config, state, action.parse()?, name).await };
+
+    AdminResponse::Timers(res().await.map(|_| None))
+}
+
+enum TimerAction {
+    Pause,
+    Resume,
+}
+
+impl FromStr for TimerAction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "pause" => Self::Pause,
+            "resume" => Self::Resume,
+            s => bail!("unknown action `{}`", s),
+        })
+    }
+}
+
+async fn update_timers(
+    config: &Config,
+    state: AsyncState,
+    action: TimerAction,
+    name: &str,
+) -> Result<()> {
+    ensure!(
+        config.timers.contains_key(name),
+        "there is no timer named `{}`",
+        name,
+    );
+
+    let mut state = state.write().await;
+    match action {
+        TimerAction::Pause => {
+            state.paused_timers.insert(name.to_owned());
+        }
+        TimerAction::Resume => {
+            state.paused_timers.remove(name);
+        }
+    }
+
+    state.persist(Change::Timers)?;
+
+    Ok(())
+}
+
+pub async fn quotes(state: AsyncState, content: &str, action: &str, number: &str) -> AdminResponse {
+    info!("admin: received `quotes` command");
+
+    let text = Some(skip_words(content, 3)).filter(|text| !text.is_empty());
+
+    let res = || async {
+        let number = number
+            .parse::<u32>()
+            .map_err(|_| anyhow!("`{}` is not a valid quote number", number))?;
+        let mut state = state.write().await;
+
+        match action {
+            "delete" => ensure!(state.quotes.remove(number), "there is no quote #{}", number),
+            "edit" => {
+                let text = text.ok_or_else(|| anyhow!("the new text of the quote is missing"))?;
+                let quote = state
+                    .quotes
+                    .get_mut(number)
+                    .ok_or_else(|| anyhow!("there is no quote #{}", number))?;
+                quote.text = text.to_owned();
+            }
+            _ => bail!("unknown action `{}`", action),
+        }
+
+        state.persist(Change::Quote(number))
+    };
+
+    AdminResponse::Quotes(res().await)
+}
+
+pub async fn roles_list(state: AsyncState) -> AdminResponse {
+    info!("admin: received `roles list` command");
+
+    AdminResponse::Roles(Ok(Some(state.read().await.reaction_roles.clone())))
+}
+
+pub async fn roles(state: AsyncState, content: &str, action: &str, value: &str) -> AdminResponse {
+    info!("admin: received `roles` command");
+
+    let rest = skip_words(content, 3);
+    let role = rest.split_whitespace().next();
+    let description = skip_words(rest, 1);
+
+    let res = || async {
+        let mut state = state.write().await;
+        let roles = &mut state.reaction_roles;
+
+        match action {
+            "channel" => {
+                roles.channel = Some(parse_id(value, "channel")?);
+                // The message is posted again in the new channel.
+                roles.message = None;
+            }
+            "add" => {
+                let role = role.ok_or_else(|| anyhow!("the role ID is missing"))?;
+                ensure!(
+                    !description.is_empty(),
+                    "the description of the role is missing"
+                );
+                let role = ReactionRole {
+                    emoji: value.to_owned(),
+                    role: parse_id(role, "role")?,
+                    description: description.to_owned(),
+                };
+                ensure!(
+                    role.custom_emoji().is_some() || !value.chars().any(char::is_alphanumeric),
+                    "`{}` is not an emoji",
+                    value
+                );
+
+                match roles.roles.iter_mut().find(|r| r.emoji == role.emoji) {
+                    Some(current) => *current = role,
+                    None => roles.roles.push(role),
+                }
+            }
+            "remove" => {
+                let count = roles.roles.len();
+                roles.roles.retain(|role| role.emoji != value);
+                ensure!(roles.roles.len() < count, "there is no role for {}", value);
+            }
+            _ => bail!("unknown action `{}`", action),
+        }
+
+        state.persist(Change::ReactionRoles)
+    };
+
+    AdminResponse::Roles(res().await.map(|()| None))
+}
+
+/// Parse a Discord ID, that may also be given as mention like `<#123>` for channels or `<@&123>`
+/// for roles.
+fn parse_id(value: &str, kind: &str) -> Result<u64> {
+    value
+        .strip_prefix("<#")
+        .or_else(|| value.strip_prefix("<@&"))
+        .and_then(|id| id.strip_suffix('>'))
+        .unwrap_or(value)
+        .parse()
+        .map_err(|_| anyhow!("`{}` is not a valid {} ID", value, kind))
+}
+
+pub fn state_backups(dirs: &DataDir) -> AdminResponse {
+    info!("admin: received `state backups` command");
+
+    let res = || backup::list(&dirs.backups()?).map(StateReport::Backups);
+
+    AdminResponse::State(res())
+}
+
+pub async fn state_export(
+    state: AsyncState,
+    dirs: &DataDir,
+    format: Option<&str>,
+) -> AdminResponse {
+    info!("admin: received `state export` command");
+
+    let res = || async {
+        let format = format.map_or(Ok(Format::Json), str::parse)?;
+        let now = Utc::now();
+        let name = format!(
+            "export-{}.{}",
+            now.format("%Y%m%d-%H%M%S"),
+            format.extension()
+        );
+
+        let bundle = backup::export(&*state.read().await, format, now)?;
+        fs::write(dirs.backups()?.join(&name), bundle)?;
+
+        Ok(StateReport::Exported(name))
+    };
+
+    AdminResponse::State(res().await)
+}
+
+pub async fn state_import(
+    state: AsyncState,
+    dirs: &DataDir,
+    name: &str,
+    mode: Option<&str>,
+) -> AdminResponse {
+    info!("admin: received `state import` command");
+
+    let res = || async {
+        let dry_run = match mode {
+            None => false,
+            Some("dry-run") => true,
+            Some(mode) => bail!("unknown import mode `{}`", mode),
+        };
+        ensure!(
+            Path::new(name).file_name() == Some(OsStr::new(name)),
+            "`{}` is not the name of a file in the backup directory",
+            name
+        );
+
+        let incoming = backup::read(&dirs.backups()?.join(name))?;
+        let mut state = state.write().await;
+        let changes = backup::merge(&mut state, incoming, dry_run)?;
+
+        if !dry_run && !changes.is_empty() {
+            state.persist(Change::All)?;
+        }
+
+        Ok(StateReport::Imported { changes, dry_run })
+    };
+
+    AdminResponse::State(res().await)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn parse_command_options() {
+        let (options, content) = CommandOptions::parse(
+            r#"--args string --cooldown 30 --alias hi,hey --args "url?https://lib.rs/{}" Hello {}!"#,
+        )
+        .unwrap();
+
+        assert_eq!(Some("Hello {}!"), content);
+        assert_eq!(2, options.args.as_ref().unwrap().len());
+        assert_eq!(
+            Cooldown::Set(NonZeroU32::new(30).unwrap()),
+            options.cooldown
+        );
+        assert_eq!(
+            Some(vec!["hi".to_owned(), "hey".to_owned()]),
+            options.aliases
+        );
+    }
+
+    #[test]
+    fn skip_leading_words() {
+        let content = "!schedule  extra 2021-01-01 14:00   16:00  \"Special  stream\" ";
+        assert_eq!(r#""Special  stream""#, skip_words(content, 5));
+        assert_eq!("", skip_words("!schedule extra 2021-01-01 14:00 16:00", 5));
+        assert_eq!("", skip_words("!schedule", 5));
+    }
+
+    #[test]
+    fn parse_dates() {
+        assert_eq!(
+            NaiveDate::from_ymd(2021, 1, 1),
+            parse_date("2021-01-01").unwrap()
+        );
+        assert_eq!(
+            "the date 2021-01-01 is in the past",
+            parse_future_date("2021-01-01").unwrap_err().to_string()
+        );
+        assert_eq!(
+            "invalid date `01.01.2021`, expected `YYYY-MM-DD`",
+            parse_date("01.01.2021").unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn parse_ids() {
+        assert_eq!(123, parse_id("123", "role").unwrap());
+        assert_eq!(123, parse_id("<@&123>", "role").unwrap());
+        assert_eq!(456, parse_id("<#456>", "channel").unwrap());
+        assert!(parse_id("<@123>", "role").is_err());
+    }
+
+    #[test]
+    fn parse_command_options_reset() {
+        let (options, content) =
+            CommandOptions::parse("--cooldown 0 --args none --alias none").unwrap();
+        let mut command = Command {
+            cooldown: NonZeroU32::new(10),
+            aliases: Some(vec!["hi".to_owned()]),
+            ..Command::empty()
+        };
+        options.apply(&mut command);
+
+        assert_eq!(None, content);
+        assert!(command.args.is_none());
+        assert!(command.cooldown.is_none());
+        assert!(command.aliases.is_none());
+    }
+}
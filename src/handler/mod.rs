@@ -1,25 +1,73 @@
 //! Main handling logic for all supported bot commands.
 
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
 use anyhow::{bail, Result};
-use tokio::sync::RwLock;
+use chrono::{DateTime, Duration, Utc};
+use log::info;
 
 use crate::{
-    settings::{Config, State},
-    AdminResponse, Message, UserResponse,
+    commands::{
+        playground::{Cooldowns, Mode},
+        Type,
+    },
+    http::Http,
+    settings::{Argument, ArgumentMode, Arity, Command, CommandItem, Config, DataDir, SharedState},
+    AdminResponse, CommandInfo, Message, Source, UserResponse,
 };
 
+use self::admin::CommandAction;
+
 mod admin;
 mod user;
 
-/// Convenience type alias for a [`State`] wrapped in an [`Arc`] and a [`RwLock`].
-pub type AsyncState = Arc<RwLock<State>>;
+/// Convenience type alias for a [`SharedState`] wrapped in an [`Arc`].
+pub type AsyncState = Arc<SharedState>;
+
+/// Minimum time in seconds between two error replies for the same command on the same platform,
+/// so that wrong arguments can't be used to flood the chat.
+const ERROR_REPLY_INTERVAL: i64 = 30;
+
+/// Rate limit for the replies to [`UserResponse::WrongArgs`].
+#[derive(Default)]
+pub struct ErrorReplies {
+    last: HashMap<(Source, String), DateTime<Utc>>,
+}
+
+impl ErrorReplies {
+    /// Pass the response through, unless it's an error reply for a command that already got one
+    /// recently. These are turned into [`UserResponse::Unknown`], so they are silently dropped.
+    pub fn limit(
+        &mut self,
+        source: Source,
+        resp: UserResponse,
+        now: DateTime<Utc>,
+    ) -> UserResponse {
+        let command = match &resp {
+            UserResponse::WrongArgs(e) => e.command.clone(),
+            _ => return resp,
+        };
+
+        let interval = Duration::seconds(ERROR_REPLY_INTERVAL);
+        match self.last.get(&(source, command.clone())) {
+            Some(last) if *last + interval > now => {
+                info!("dropped error reply for `{}` on {:?}", command, source);
+                UserResponse::Unknown
+            }
+            _ => {
+                self.last.insert((source, command), now);
+                resp
+            }
+        }
+    }
+}
 
 /// Handle any user facing message and prepare a response.
 pub async fn user_message(
     config: &Config,
     state: AsyncState,
+    http: &dyn Http,
+    cooldowns: &Cooldowns,
     message: Message,
 ) -> Result<UserResponse> {
     let mut parts = message.content.splitn(2, char::is_whitespace);
@@ -30,15 +78,145 @@ pub async fn user_message(
     };
 
     Ok(match (command.to_lowercase().as_ref(), parts.next()) {
-        ("!commands", None) => user::commands(config, message.source).await,
+        ("!commands", args) => user::commands(config, state, message.source, args).await,
         ("!links", None) => user::links(config, message.source),
         ("!schedule", None) => user::schedule(state).await,
-        (name, args) => user::custom(&config, state, message.source, name, args).await,
+        ("!quote", args) => user::quote(state, args).await,
+        ("!play", args) => user::play(config, http, cooldowns, &message, Mode::Play, args).await,
+        ("!eval", args) => user::play(config, http, cooldowns, &message, Mode::Eval, args).await,
+        ("!addquote", Some(text)) if message.admin || message.moderator => {
+            user::add_quote(state, message.source, &message.author, text).await
+        }
+        // The built-in lookup is only a fallback, so a custom `crate` command replaces it.
+        ("!crate", args) if !user::is_custom(config, &state, "crate").await => {
+            user::crate_info(config, http, args).await
+        }
+        (name, args) => {
+            let privileged = message.admin || message.moderator;
+            user::custom(config, state, http, message.source, privileged, name, args).await
+        }
     })
 }
 
+/// Handle the conventional chat commands `!addcom`, `!editcom` and `!delcom` that allow platform
+/// moderators to manage custom commands without being bot admins. Returns [`None`] if the message
+/// didn't come from a moderator or isn't one of these commands.
+pub async fn moderator_message(
+    config: &Config,
+    state: AsyncState,
+    message: &Message,
+) -> Option<AdminResponse> {
+    if !message.moderator {
+        return None;
+    }
+
+    let mut parts = message.content.splitn(3, char::is_whitespace);
+    let action = match parts.next()?.to_lowercase().as_ref() {
+        "!addcom" => CommandAction::Add,
+        "!editcom" => CommandAction::Edit,
+        "!delcom" => CommandAction::Remove,
+        _ => return None,
+    };
+
+    Some(
+        admin::chat_command(
+            config,
+            state,
+            action,
+            parts.next().unwrap_or_default(),
+            parts.next().unwrap_or_default(),
+        )
+        .await,
+    )
+}
+
+/// Describe all commands that users can call on the given platform, for platforms that register
+/// commands up front. Admin commands are combined into a single `admin` command, that takes the
+/// admin command as text.
+pub async fn command_list(config: &Config, state: &AsyncState, source: Source) -> Vec<CommandInfo> {
+    let info = |name: &str, description: String, args| CommandInfo {
+        name: name.to_owned(),
+        description,
+        args,
+        admin: false,
+    };
+    let optional = |name: &str, ty| Argument {
+        name: Some(name.to_owned()),
+        mode: ArgumentMode::Simple,
+        ty,
+        arity: Arity::Optional,
+        error: None,
+    };
+
+    let mut list = vec![
+        info(
+            "commands",
+            "List all available commands".to_owned(),
+            vec![optional(
+                "page",
+                Type::Int {
+                    min: Some(1),
+                    max: None,
+                },
+            )],
+        ),
+        info("links", "Links to the streamer's sites".to_owned(), vec![]),
+        info("schedule", "The streaming schedule".to_owned(), vec![]),
+    ];
+
+    // Commands without declared arguments get everything after their name as single argument.
+    let args = |command: &Command| {
+        command.args.clone().unwrap_or_else(|| {
+            vec![Argument {
+                arity: Arity::Rest,
+                ..optional("text", Type::String(1))
+            }]
+        })
+    };
+
+    for (name, item) in &config.commands {
+        match item {
+            CommandItem::Message(_) => list.push(info(name, format!("!{}", name), vec![])),
+            CommandItem::Custom(command) if command.platforms.contains(&source) => {
+                list.push(info(name, command.usage(name), args(command)));
+            }
+            CommandItem::Counter(counter) if counter.platforms.contains(&source) => {
+                list.push(info(name, format!("!{}", name), vec![]));
+            }
+            _ => {}
+        }
+    }
+
+    for (name, command) in &state.read().await.custom_commands {
+        if command.platforms.contains(&source) {
+            list.push(info(name, command.usage(name), args(command)));
+        }
+    }
+
+    list.push(CommandInfo {
+        admin: true,
+        ..info(
+            "admin",
+            "Run an admin command, like `schedule skip 2021-06-01`".to_owned(),
+            vec![Argument {
+                arity: Arity::Rest,
+                ..optional("command", Type::String(1))
+            }],
+        )
+    });
+
+    list.sort_by(|a, b| a.name.cmp(&b.name));
+    list.dedup_by(|a, b| a.name == b.name);
+    list
+}
+
 /// Handle admin facing messages to control the bot and prepare a response.
-pub async fn admin_message(state: AsyncState, content: String) -> Result<AdminResponse> {
+pub async fn admin_message(
+    config: &Config,
+    state: AsyncState,
+    dirs: &DataDir,
+    content: String,
+) -> Result<AdminResponse> {
     let mut parts = content.split_whitespace();
     let command = if let Some(cmd) = parts.next() {
         cmd
@@ -58,6 +236,18 @@ pub async fn admin_message(state: AsyncState, content: String) -> Result<AdminRe
             ("!schedule", Some("set"), Some(field), Some(range_begin), Some(range_end)) => {
                 admin::schedule(state, field, range_begin, range_end).await
             }
+            ("!schedule", Some("skip"), Some(date), None, None) => {
+                admin::schedule_skip(state, date).await
+            }
+            ("!schedule", Some("extra"), Some(date), Some(start), Some(finish)) => {
+                admin::schedule_extra(state, &content, date, start, finish).await
+            }
+            ("!schedule", Some("vacation"), Some(range), None, None) => {
+                admin::schedule_vacation(state, range).await
+            }
+            ("!schedule", Some("remove"), Some(date), None, None) => {
+                admin::schedule_remove(state, date).await
+            }
             ("!off_days", Some(action), Some(weekday), None, None) => {
                 admin::off_days(state, action, weekday).await
             }
@@ -65,9 +255,62 @@ pub async fn admin_message(state: AsyncState, content: String) -> Result<AdminRe
                 admin::custom_commands_list(state).await
             }
             ("!custom_commands", Some(action), Some(source), Some(name), _) => {
-                admin::custom_commands(state, &content, action, source, name).await
+                admin::custom_commands(config, state, &content, action, source, name).await
+            }
+            ("!timers", Some("list"), None, None, None) => admin::timers_list(config, state).await,
+            ("!timers", Some(action), Some(name), None, None) => {
+                admin::timers(config, state, action, name).await
+            }
+            ("!quotes", Some(action), Some(number), _, _) => {
+                admin::quotes(state, &content, action, number).await
+            }
+            ("!roles", Some("list"), None, None, None) => admin::roles_list(state).await,
+            ("!roles", Some(action), Some(value), _, _) => {
+                admin::roles(state, &content, action, value).await
+            }
+            ("!state", Some("backups"), None, None, None) => admin::state_backups(dirs),
+            ("!state", Some("export"), format, None, None) => {
+                admin::state_export(state, dirs, format).await
+            }
+            ("!state", Some("import"), Some(name), mode, None) => {
+                admin::state_import(state, dirs, name, mode).await
             }
             _ => AdminResponse::Unknown,
         },
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::ArgsError;
+
+    fn wrong_args(command: &str) -> UserResponse {
+        UserResponse::WrongArgs(ArgsError {
+            command: command.to_owned(),
+            usage: format!("!{}", command),
+            argument: None,
+            reason: "missing <string>".to_owned(),
+            message: None,
+        })
+    }
+
+    #[test]
+    fn limit_error_replies() {
+        let mut replies = ErrorReplies::default();
+        let start = Utc.ymd(2021, 6, 1).and_hms(12, 0, 0);
+        let mut passed = |source, command, seconds| {
+            let now = start + Duration::seconds(seconds);
+            let resp = replies.limit(source, wrong_args(command), now);
+            matches!(resp, UserResponse::WrongArgs(_))
+        };
+
+        assert!(passed(Source::Twitch, "crate", 0));
+        assert!(!passed(Source::Twitch, "crate", 10));
+        assert!(passed(Source::Discord, "crate", 10));
+        assert!(passed(Source::Twitch, "docs", 10));
+        assert!(passed(Source::Twitch, "crate", 30));
+    }
+}
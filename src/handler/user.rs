@@ -1,16 +1,50 @@
-use chrono::Weekday;
-use log::info;
+use chrono::{Utc, Weekday};
+use log::{info, warn};
+use rand::seq::SliceRandom;
 
 use super::AsyncState;
-use crate::{Source, UserResponse, settings::{Command, CommandItem, Config}};
+use crate::{
+    commands::{
+        crates,
+        playground::{self, Cooldowns, Mode},
+    },
+    http::Http,
+    settings::{self, Change, Command, CommandItem, Config, Counter},
+    ArgsError, Message, Source, UserResponse,
+};
 
-pub async fn commands(config: &Config, source: Source) -> UserResponse {
+pub async fn commands(
+    config: &Config,
+    state: AsyncState,
+    source: Source,
+    args: Option<&str>,
+) -> UserResponse {
     info!("user: received `commands` command");
-    UserResponse::Commands(Ok(list_command_names(config, source).await))
+
+    let page = match args.map(str::trim).filter(|args| !args.is_empty()) {
+        None => 1,
+        Some(page) => match page.parse::<usize>() {
+            Ok(page) if page > 0 => page,
+            _ => {
+                return UserResponse::WrongArgs(ArgsError {
+                    command: "commands".to_owned(),
+                    usage: "!commands [page]".to_owned(),
+                    argument: Some("[page]".to_owned()),
+                    reason: format!("`{}` is not a valid [page]", page),
+                    message: None,
+                })
+            }
+        },
+    };
+
+    UserResponse::Commands {
+        names: Ok(list_command_names(config, state, source).await),
+        page,
+    }
 }
 
-async fn list_command_names(config: &Config, source: Source) -> Vec<String> {
-    config
+async fn list_command_names(config: &Config, state: AsyncState, source: Source) -> Vec<String> {
+    let mut names = config
         .commands
         .iter()
         .filter_map(|(name, ci)| match ci {
@@ -27,9 +61,34 @@ async fn list_command_names(config: &Config, source: Source) -> Vec<String> {
                 0 => Some(name.to_string()),
                 _ => Some(format!("{} (or !{})", name, aliases.join(", !"))),
             },
+            CommandItem::Counter(Counter {
+                aliases, platforms, ..
+            }) if platforms.contains(&source) => match aliases {
+                Some(aliases) if !aliases.is_empty() => {
+                    Some(format!("{} (or !{})", name, aliases.join(", !")))
+                }
+                _ => Some(name.to_string()),
+            },
             _ => None,
         })
-        .collect()
+        .collect::<Vec<_>>();
+
+    let state = state.read().await;
+    let mut custom = state
+        .custom_commands
+        .iter()
+        .filter(|(_, command)| command.platforms.contains(&source))
+        .map(|(name, command)| match &command.aliases {
+            Some(aliases) if !aliases.is_empty() => {
+                format!("{} (or !{})", name, aliases.join(", !"))
+            }
+            _ => name.clone(),
+        })
+        .collect::<Vec<_>>();
+    custom.sort();
+
+    names.extend(custom);
+    names
 }
 
 pub fn links(config: &Config, source: Source) -> UserResponse {
@@ -64,42 +123,160 @@ pub async fn schedule(state: AsyncState) -> UserResponse {
                 .to_owned()
             })
             .collect(),
+        exceptions: state.schedule_exceptions.describe(settings::today()),
     }
 }
 
 pub async fn custom(
     config: &Config,
     state: AsyncState,
+    http: &dyn Http,
     source: Source,
+    privileged: bool,
     name: &str,
     args: Option<&str>,
 ) -> UserResponse {
     if let Some(name) = name.strip_prefix('!') {
         info!("{:?}", args);
-        if let Some((cn, ci)) = config.commands.iter().find(|(key, val)| {
-            if name.eq_ignore_ascii_case(key) {
-                true
-            } else {
-                if let CommandItem::Custom(Command {
-                    aliases: Some(aliases),
-                    ..
-                }) = val
-                {
-                    aliases.iter().any(|a| a.eq_ignore_ascii_case(name))
-                } else {
-                    false
-                }
-            }
-        }) {
+        if let Some((cn, ci)) = config
+            .commands
+            .iter()
+            .find(|(key, val)| val.matches(key, name))
+        {
             match ci {
                 CommandItem::Message(m) => UserResponse::Custom(m.clone()),
-                CommandItem::Custom(c) => c.respond(cn, args, state, source).await,
-                CommandItem::Function(..) => todo!()
+                CommandItem::Counter(c) => c.respond(cn, args, state, source, privileged).await,
+                CommandItem::Custom(c) => c.respond(cn, args, state, source, http).await,
+                CommandItem::Function(..) => todo!(),
             }
         } else {
-            UserResponse::Unknown
+            // Runtime commands come last, so configured commands always take precedence.
+            let command = state
+                .read()
+                .await
+                .custom_commands
+                .iter()
+                .find(|(key, command)| command.matches(key, name))
+                .map(|(key, command)| (key.clone(), command.clone()));
+
+            if let Some((cn, command)) = command {
+                command.respond(&cn, args, state, source, http).await
+            } else {
+                UserResponse::Unknown
+            }
         }
     } else {
         UserResponse::Unknown
     }
 }
+
+/// Whether a custom command with the given name or alias exists, either configured or added at
+/// runtime.
+pub async fn is_custom(config: &Config, state: &AsyncState, name: &str) -> bool {
+    config
+        .commands
+        .iter()
+        .any(|(key, val)| val.matches(key, name))
+        || state
+            .read()
+            .await
+            .custom_commands
+            .iter()
+            .any(|(key, command)| command.matches(key, name))
+}
+
+pub async fn quote(state: AsyncState, args: Option<&str>) -> UserResponse {
+    info!("user: received `quote` command");
+
+    let state = state.read().await;
+    let quotes = &state.quotes;
+    let mut rng = rand::thread_rng();
+
+    let quote = match args.map(str::trim).filter(|args| !args.is_empty()) {
+        None => quotes.all().choose(&mut rng),
+        Some(args) => match args.trim_start_matches('#').parse() {
+            Ok(number) => quotes.get(number),
+            Err(_) => quotes.search(args).choose(&mut rng).copied(),
+        },
+    };
+
+    UserResponse::Quote(quote.cloned())
+}
+
+pub async fn add_quote(
+    state: AsyncState,
+    source: Source,
+    author: &str,
+    text: &str,
+) -> UserResponse {
+    info!("user: received `addquote` command");
+
+    let mut state = state.write().await;
+    let number = state.quotes.add(
+        text.trim().to_owned(),
+        author.to_owned(),
+        settings::today(),
+        source,
+    );
+
+    if let Err(e) = state.persist(Change::Quote(number)) {
+        warn!("failed saving quote #{}: {}", number, e);
+    }
+
+    UserResponse::Custom(format!("Added quote #{}", number))
+}
+
+pub async fn crate_info(config: &Config, http: &dyn Http, args: Option<&str>) -> UserResponse {
+    info!("user: received `crate` command");
+
+    match args.and_then(|args| args.split_whitespace().next()) {
+        Some(name) => UserResponse::Crate(crates::lookup(&config.crates, name, http).await),
+        None => UserResponse::WrongArgs(ArgsError {
+            command: "crate".to_owned(),
+            usage: "!crate <name>".to_owned(),
+            argument: Some("<name>".to_owned()),
+            reason: "missing <name>".to_owned(),
+            message: None,
+        }),
+    }
+}
+
+pub async fn play(
+    config: &Config,
+    http: &dyn Http,
+    cooldowns: &Cooldowns,
+    message: &Message,
+    mode: Mode,
+    args: Option<&str>,
+) -> UserResponse {
+    let command = match mode {
+        Mode::Play => "play",
+        Mode::Eval => "eval",
+    };
+    info!("user: received `{}` command", command);
+
+    let options = &config.playground;
+    let code = playground::extract(args.unwrap_or_default());
+    let reason = if code.is_empty() {
+        "missing <code>".to_owned()
+    } else if code.len() > options.max_code {
+        format!("the code is longer than {} bytes", options.max_code)
+    } else if cooldowns.start(
+        message.source,
+        &message.author,
+        options.cooldown,
+        Utc::now(),
+    ) {
+        return UserResponse::Play(playground::run(options, mode, code, http).await);
+    } else {
+        return UserResponse::Unknown;
+    };
+
+    UserResponse::WrongArgs(ArgsError {
+        command: command.to_owned(),
+        usage: format!("!{} <code>", command),
+        argument: Some("<code>".to_owned()),
+        reason,
+        message: None,
+    })
+}
@@ -0,0 +1,467 @@
+//! Shared HTTP client for all outgoing requests that are triggered by chat commands.
+//!
+//! Results are cached, so spamming a command with the same argument doesn't result in the same
+//! request over and over, and the number of concurrent requests to each host is limited.
+
+use std::{
+    borrow::Borrow,
+    collections::HashMap,
+    fmt::{self, Display},
+    hash::Hash,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, ensure, Result};
+use futures_util::future::BoxFuture;
+use log::{info, warn};
+use reqwest::{
+    header::{ACCEPT, CONTENT_TYPE},
+    Client, RequestBuilder, StatusCode, Url,
+};
+use serde_json::Value;
+use tokio::sync::Semaphore;
+
+/// Time to wait for a connection to be established.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+/// Time to wait for a whole request, including the connection.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Time to wait for requests that make the server do some work, like compiling code.
+const POST_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long a link is remembered as reachable.
+const POSITIVE_TTL: Duration = Duration::from_secs(60 * 60);
+/// How long a link is remembered as unreachable, which is shorter as crates or repositories might
+/// be created any moment.
+const NEGATIVE_TTL: Duration = Duration::from_secs(5 * 60);
+/// Maximum number of links in the cache.
+const CACHE_CAPACITY: usize = 512;
+/// Maximum number of concurrent requests to a single host.
+const HOST_CONCURRENCY: usize = 2;
+
+/// Maximum size of a JSON document, to not buffer arbitrarily large responses.
+const MAX_JSON_SIZE: usize = 1024 * 1024;
+
+/// Error for a JSON document that doesn't exist, so callers can tell it apart from other failures
+/// like timeouts or server errors.
+#[derive(Debug)]
+pub struct LinkNotFound(pub String);
+
+impl Display for LinkNotFound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}` not found", self.0)
+    }
+}
+
+impl std::error::Error for LinkNotFound {}
+
+/// Access to remote resources over HTTP, which can be replaced with a fake in tests.
+pub trait Http: Send + Sync {
+    /// Check whether the link exists, following any redirects.
+    fn reachable<'a>(&'a self, link: &'a str) -> BoxFuture<'a, bool>;
+
+    /// Get the JSON document at the link, which is cached for the given time if the request was
+    /// successful. Fails with [`LinkNotFound`] if the document doesn't exist.
+    fn json<'a>(&'a self, link: &'a str, ttl: Duration) -> BoxFuture<'a, Result<Arc<Value>>>;
+
+    /// Send the JSON document to the link and get back the JSON response, which is never cached.
+    fn post_json<'a>(&'a self, link: &'a str, body: &'a Value) -> BoxFuture<'a, Result<Value>>;
+}
+
+/// The [`Http`] implementation used by the bot, which caches results and limits concurrent
+/// requests per host.
+pub struct CachingClient {
+    client: Client,
+    cache: Mutex<Cache<String, bool>>,
+    documents: Mutex<Cache<String, Arc<Value>>>,
+    hosts: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+impl CachingClient {
+    pub fn new() -> Result<Self> {
+        let client = Client::builder()
+            .user_agent("ToggleBot")
+            .connect_timeout(CONNECT_TIMEOUT)
+            .timeout(REQUEST_TIMEOUT)
+            .build()?;
+
+        Ok(Self {
+            client,
+            cache: Mutex::new(Cache::new(CACHE_CAPACITY)),
+            documents: Mutex::new(Cache::new(CACHE_CAPACITY)),
+            hosts: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Get the limit of concurrent requests for the host, creating it on first use.
+    fn host_limit(&self, host: &str) -> Arc<Semaphore> {
+        self.hosts
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(host.to_owned())
+            .or_insert_with(|| Arc::new(Semaphore::new(HOST_CONCURRENCY)))
+            .clone()
+    }
+
+    /// Check the link with a `HEAD` request first, and fall back to a `GET` request for servers
+    /// that don't answer those properly. Returns [`None`] if the server couldn't be reached at
+    /// all, for example due to a timeout.
+    async fn request(&self, url: Url) -> Option<bool> {
+        let limit = self.host_limit(url.host_str().unwrap_or_default());
+        let _permit = limit.acquire().await.ok()?;
+
+        info!("Trying to reach: {}", url);
+        match self.client.head(url.clone()).send().await {
+            Ok(resp) if resp.status().is_success() => return Some(true),
+            Ok(resp) if matches!(resp.status(), StatusCode::NOT_FOUND | StatusCode::GONE) => {
+                return Some(false)
+            }
+            Ok(_) | Err(_) => {}
+        }
+
+        match self.client.get(url).send().await {
+            Ok(resp) => Some(resp.status().is_success()),
+            Err(e) => {
+                warn!("failed reaching link: {}", e);
+                None
+            }
+        }
+    }
+
+    async fn request_json(&self, url: Url) -> Result<Value> {
+        let limit = self.host_limit(url.host_str().unwrap_or_default());
+        let _permit = limit.acquire().await?;
+
+        info!("Fetching: {}", url);
+        Self::read_json(self.client.get(url)).await
+    }
+
+    async fn send_json(&self, url: Url, body: &Value) -> Result<Value> {
+        let limit = self.host_limit(url.host_str().unwrap_or_default());
+        let _permit = limit.acquire().await?;
+
+        info!("Posting to: {}", url);
+        let request = self
+            .client
+            .post(url)
+            .timeout(POST_TIMEOUT)
+            .header(CONTENT_TYPE, "application/json")
+            .body(serde_json::to_vec(body)?);
+
+        Self::read_json(request).await
+    }
+
+    async fn read_json(request: RequestBuilder) -> Result<Value> {
+        let response = request.header(ACCEPT, "application/json").send().await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            bail!(LinkNotFound(response.url().to_string()));
+        }
+        let mut response = response.error_for_status()?;
+
+        if let Some(length) = response.content_length() {
+            ensure!(
+                length <= MAX_JSON_SIZE as u64,
+                "response of {} bytes is too large",
+                length
+            );
+        }
+
+        // The announced length might be missing or wrong, so the limit is checked while reading as
+        // well, stopping as soon as it's exceeded.
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            ensure!(
+                body.len() + chunk.len() <= MAX_JSON_SIZE,
+                "response is larger than {} bytes",
+                MAX_JSON_SIZE
+            );
+            body.extend_from_slice(&chunk);
+        }
+
+        serde_json::from_slice(&body).map_err(Into::into)
+    }
+}
+
+impl Http for CachingClient {
+    fn reachable<'a>(&'a self, link: &'a str) -> BoxFuture<'a, bool> {
+        Box::pin(async move {
+            let cached = self
+                .cache
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .get(link, Instant::now());
+            if let Some(reachable) = cached {
+                return reachable;
+            }
+
+            let url = match Url::parse(link) {
+                Ok(url) => url,
+                Err(_) => return false,
+            };
+
+            match self.request(url).await {
+                Some(reachable) => {
+                    let ttl = if reachable {
+                        POSITIVE_TTL
+                    } else {
+                        NEGATIVE_TTL
+                    };
+                    self.cache
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .insert(link.to_owned(), reachable, ttl, Instant::now());
+                    reachable
+                }
+                None => false,
+            }
+        })
+    }
+
+    fn json<'a>(&'a self, link: &'a str, ttl: Duration) -> BoxFuture<'a, Result<Arc<Value>>> {
+        Box::pin(async move {
+            let cached = self
+                .documents
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .get(link, Instant::now());
+            if let Some(document) = cached {
+                return Ok(document);
+            }
+
+            let document = Arc::new(self.request_json(Url::parse(link)?).await?);
+            self.documents
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .insert(link.to_owned(), document.clone(), ttl, Instant::now());
+
+            Ok(document)
+        })
+    }
+
+    fn post_json<'a>(&'a self, link: &'a str, body: &'a Value) -> BoxFuture<'a, Result<Value>> {
+        Box::pin(async move { self.send_json(Url::parse(link)?, body).await })
+    }
+}
+
+/// Fake [`Http`] implementation for tests, that only knows a fixed set of links.
+#[cfg(test)]
+#[derive(Default)]
+pub struct FakeHttp {
+    pub links: Vec<String>,
+    /// JSON documents by their link, which are reachable as well and also returned for posts.
+    pub documents: Vec<(String, Value)>,
+    /// Bodies of all posted documents.
+    pub posted: Mutex<Vec<Value>>,
+}
+
+#[cfg(test)]
+impl Http for FakeHttp {
+    fn reachable<'a>(&'a self, link: &'a str) -> BoxFuture<'a, bool> {
+        Box::pin(async move {
+            self.links.iter().any(|l| l == link) || self.documents.iter().any(|(l, _)| l == link)
+        })
+    }
+
+    fn json<'a>(&'a self, link: &'a str, _ttl: Duration) -> BoxFuture<'a, Result<Arc<Value>>> {
+        Box::pin(async move {
+            self.documents
+                .iter()
+                .find(|(l, _)| l == link)
+                .map(|(_, document)| Arc::new(document.clone()))
+                .ok_or_else(|| LinkNotFound(link.to_owned()).into())
+        })
+    }
+
+    fn post_json<'a>(&'a self, link: &'a str, body: &'a Value) -> BoxFuture<'a, Result<Value>> {
+        Box::pin(async move {
+            self.posted
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(body.clone());
+            self.json(link, Duration::default())
+                .await
+                .map(|document| (*document).clone())
+        })
+    }
+}
+
+/// A cache with limited capacity, that evicts the least recently used entry when full and
+/// expires entries after their time to live.
+pub(crate) struct Cache<K, V> {
+    capacity: usize,
+    entries: HashMap<K, Entry<V>>,
+    /// Counter that is increased with each access, to find the least recently used entry.
+    tick: u64,
+}
+
+struct Entry<V> {
+    value: V,
+    expires: Instant,
+    used: u64,
+}
+
+impl<K: Clone + Eq + Hash, V: Clone> Cache<K, V> {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            entries: HashMap::new(),
+            tick: 0,
+        }
+    }
+
+    pub(crate) fn get<Q>(&mut self, key: &Q, now: Instant) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.tick += 1;
+
+        match self.entries.get_mut(key) {
+            Some(entry) if entry.expires > now => {
+                entry.used = self.tick;
+                Some(entry.value.clone())
+            }
+            Some(_) => {
+                self.entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub(crate) fn insert(&mut self, key: K, value: V, ttl: Duration, now: Instant) {
+        self.tick += 1;
+
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&key) {
+            self.entries.retain(|_, entry| entry.expires > now);
+        }
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&key) {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+
+        self.entries.insert(
+            key,
+            Entry {
+                value,
+                expires: now + ttl,
+                used: self.tick,
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        sync::atomic::{AtomicUsize, Ordering},
+        thread,
+    };
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    /// Start a minimal HTTP server on a random local port, that answers `/crates/serde` with a JSON
+    /// document, `/large` with an oversized one and everything else with a 404. Returns its
+    /// address and the count of requests.
+    fn mock_server() -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request = String::new();
+                reader.read_line(&mut request).unwrap();
+                // Skip the headers, as requests never have a body.
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                counter.fetch_add(1, Ordering::SeqCst);
+
+                let (status, body) = if request.starts_with("GET /crates/serde ") {
+                    (
+                        "200 OK",
+                        r#"{"crate":{"max_version":"1.0.126"}}"#.to_owned(),
+                    )
+                } else if request.starts_with("GET /large ") {
+                    ("200 OK", format!("\"{}\"", "a".repeat(MAX_JSON_SIZE)))
+                } else {
+                    ("404 Not Found", "{}".to_owned())
+                };
+                write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+                     Connection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                )
+                .unwrap();
+            }
+        });
+
+        (addr, requests)
+    }
+
+    #[tokio::test]
+    async fn json_cached() {
+        let (addr, requests) = mock_server();
+        let client = CachingClient::new().unwrap();
+        let link = format!("{}/crates/serde", addr);
+        let ttl = Duration::from_secs(60);
+
+        for _ in 0..2 {
+            let document = client.json(&link, ttl).await.unwrap();
+            assert_eq!("1.0.126", document["crate"]["max_version"]);
+        }
+        assert_eq!(1, requests.load(Ordering::SeqCst));
+
+        let missing = format!("{}/crates/sered", addr);
+        assert!(client
+            .json(&missing, ttl)
+            .await
+            .unwrap_err()
+            .is::<LinkNotFound>());
+        assert!(client.json(&missing, ttl).await.is_err());
+        assert_eq!(3, requests.load(Ordering::SeqCst));
+
+        let large = client
+            .json(&format!("{}/large", addr), ttl)
+            .await
+            .unwrap_err();
+        assert!(large.to_string().contains("too large"));
+    }
+
+    #[test]
+    fn cache_expiry_and_eviction() {
+        let now = Instant::now();
+        let ttl = Duration::from_secs(60);
+        let mut cache = Cache::new(2);
+
+        cache.insert("a", true, ttl, now);
+        cache.insert("b", false, ttl, now);
+        assert_eq!(Some(true), cache.get(&"a", now));
+
+        // `b` was used least recently, so it's evicted first.
+        cache.insert("c", true, ttl, now);
+        assert_eq!(None, cache.get(&"b", now));
+        assert_eq!(Some(true), cache.get(&"a", now));
+
+        assert_eq!(None, cache.get(&"c", now + ttl));
+        cache.insert("d", false, Duration::from_secs(10), now);
+        assert_eq!(Some(false), cache.get(&"d", now));
+    }
+}
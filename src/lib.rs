@@ -5,21 +5,36 @@
 #![warn(clippy::nursery)]
 #![allow(clippy::missing_errors_doc)]
 
+use std::{collections::HashMap, sync::Arc};
+
 /// Result type used throughout the whole crate.
 pub use anyhow::Result;
 use serde::{Deserialize, Serialize};
 pub use tokio::sync::{
-    broadcast::Receiver as BroadcastReceiver, mpsc::Sender as MpscSender,
+    broadcast::Receiver as BroadcastReceiver,
+    mpsc::{Receiver as MpscReceiver, Sender as MpscSender},
     oneshot::Sender as OneshotSender,
+    watch::Receiver as WatchReceiver,
 };
 
-use crate::settings::Links;
+use crate::{
+    commands::{crates::CrateLookup, playground::Evaluation},
+    moderation::Violation,
+    settings::{Argument, Command, Embed, Links, Quote, ReactionRoles},
+};
 
+pub mod announce;
+pub mod backup;
 pub mod commands;
 pub mod discord;
 pub mod emojis;
 pub mod handler;
+pub mod http;
+pub mod logging;
+pub mod moderation;
+pub mod render;
 pub mod settings;
+pub mod timers;
 pub mod twitch;
 
 /// A queue that service connecters can use to send received messages to the handler and get back a
@@ -28,16 +43,53 @@ pub type Queue = MpscSender<(Message, OneshotSender<Response>)>;
 /// Shutdown hook that service connecters use to be notified about a shutdown and shut down all
 /// internal machinery.
 pub type Shutdown = BroadcastReceiver<()>;
+/// Receiving end of messages that a service connector should post on its own, without a user
+/// command triggering it.
+pub type Outbox = MpscReceiver<Outgoing>;
+/// The current list of commands, for service connectors that register them up front with the
+/// platform. It's updated whenever commands are added or removed.
+pub type CommandList = WatchReceiver<Vec<CommandInfo>>;
+/// Sending ends for the [`Outbox`] of each service connector, by the name of the platform
+/// instance.
+pub type Outboxes = Arc<HashMap<String, MpscSender<Outgoing>>>;
 
 /// A message that was received by a service connector. It contains all information needed by the
 /// handler to parse and act upon the message.
 pub struct Message {
     /// Tells what service connector the message came from.
     pub source: Source,
+    /// Name of the platform instance that received the message, as used for the [`Outboxes`].
+    pub platform: String,
     /// The whole message content.
     pub content: String,
     /// Whether this message is considered an admin command.
     pub admin: bool,
+    /// Whether the author has elevated rights on the platform, like Twitch moderators. They can
+    /// manage custom commands directly from chat without being admins.
+    pub moderator: bool,
+    /// Name of the user that sent the message.
+    pub author: String,
+}
+
+/// Description of a single command, like the name and arguments of a slash command on Discord.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommandInfo {
+    /// Name of the command, without the leading `!`.
+    pub name: String,
+    /// Short description of what the command does, or how to call it.
+    pub description: String,
+    pub args: Vec<Argument>,
+    /// Whether the command is meant for admins only.
+    pub admin: bool,
+}
+
+/// A message that the bot posts on its own, like stream announcements.
+#[derive(Clone, Debug)]
+pub struct Outgoing {
+    /// Channel to post to, for service connectors that serve several channels at once.
+    pub channel: Option<u64>,
+    /// The whole message content.
+    pub content: String,
 }
 
 /// Possible sources that a message came from.
@@ -64,22 +116,55 @@ pub enum Response {
     User(UserResponse),
     /// Response for an admin command.
     Admin(AdminResponse),
+    /// The message broke a moderation rule and wasn't handled any further.
+    Moderation(Violation),
 }
 
 pub enum UserResponse {
     /// Command was not recognized and should be ignored.
     Unknown,
-    WrongArgs,
-    /// List all available commands to the user.
-    Commands(Result<Vec<String>>),
+    /// The command exists, but couldn't be run with the given arguments.
+    WrongArgs(ArgsError),
+    /// List all available commands to the user, showing only the requested page if they don't
+    /// fit into a single message. Pages start at 1.
+    Commands {
+        names: Result<Vec<String>>,
+        page: usize,
+    },
     /// Show a list of links to various platforms where the streamer is present.
     Links(Links),
     Schedule {
         start: String,
         finish: String,
         off_days: Vec<String>,
+        /// Upcoming one-off changes to the regular schedule.
+        exceptions: Vec<String>,
     },
     Custom(String),
+    /// Response of a custom command as Discord embed, that other platforms show as plain text.
+    Embed(Embed),
+    /// A single quote, or [`None`] if no quote matched the request.
+    Quote(Option<Quote>),
+    /// Details about a crate from the registry, or similarly named ones if it doesn't exist.
+    Crate(Result<CrateLookup>),
+    /// Output of a code snippet that was run in the playground.
+    Play(Result<Evaluation>),
+}
+
+/// Details about why the arguments of a command couldn't be used, to tell the user how to call
+/// the command instead.
+#[derive(Debug)]
+pub struct ArgsError {
+    /// Name of the command, without the leading `!`.
+    pub command: String,
+    /// How to call the command, like `!crate <name>`.
+    pub usage: String,
+    /// Label of the argument that failed, or [`None`] if the arguments as a whole didn't fit.
+    pub argument: Option<String>,
+    /// Short description of what went wrong.
+    pub reason: String,
+    /// The error message configured for the failed argument, which replaces the usage if set.
+    pub message: Option<String>,
 }
 
 pub enum AdminResponse {
@@ -89,5 +174,23 @@ pub enum AdminResponse {
     Help,
     Schedule(Result<()>),
     OffDays(Result<()>),
-    CustomCommands(Result<Option<Vec<(String, Source, String)>>>),
+    /// Either the list of runtime custom commands, or the result of updating one of them.
+    CustomCommands(Result<Option<Vec<(String, Command)>>>),
+    /// Either the list of timers with their paused state, or the result of pausing/resuming one.
+    Timers(Result<Option<Vec<(String, bool)>>>),
+    /// Result of listing, exporting or importing state bundles.
+    State(Result<StateReport>),
+    Quotes(Result<()>),
+    /// Either the self-assignable Discord roles, or the result of changing them.
+    Roles(Result<Option<ReactionRoles>>),
+}
+
+/// Outcome of the admin commands that work with exported state bundles.
+pub enum StateReport {
+    /// File names of all bundles in the backup directory, oldest first.
+    Backups(Vec<String>),
+    /// File name of the newly exported bundle.
+    Exported(String),
+    /// Description of each change that was made to the state, or would have been in a dry run.
+    Imported { changes: Vec<String>, dry_run: bool },
 }
@@ -0,0 +1,77 @@
+//! Logging to the terminal, with a copy of every message in a log file in the data directory.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    sync::Mutex,
+};
+
+use anyhow::{Context, Result};
+use chrono::prelude::*;
+use log::{Log, Metadata, Record};
+
+use crate::settings::DataDir;
+
+/// Log file inside the `logs` folder of the data directory. New messages are appended, so it keeps
+/// the history of previous runs as well.
+const FILE_NAME: &str = "togglebot.log";
+
+struct Logger {
+    terminal: env_logger::Logger,
+    file: Mutex<File>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.terminal.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if !self.terminal.matches(record) {
+            return;
+        }
+
+        self.terminal.log(record);
+
+        if let Ok(mut file) = self.file.lock() {
+            // There is no better place to report a failed write to the log, so it's ignored.
+            writeln!(
+                file,
+                "{} {:<5} {}: {}",
+                Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+                record.level(),
+                record.target(),
+                record.args()
+            )
+            .ok();
+        }
+    }
+
+    fn flush(&self) {
+        self.terminal.flush();
+        if let Ok(mut file) = self.file.lock() {
+            file.flush().ok();
+        }
+    }
+}
+
+/// Set up the global logger, filtered by the `RUST_LOG` environment variable like before.
+pub fn init(dirs: &DataDir) -> Result<()> {
+    let path = dirs.logs()?.join(FILE_NAME);
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("failed opening log file `{}`", path.display()))?;
+
+    let terminal = env_logger::Builder::from_default_env().build();
+    let max_level = terminal.filter();
+
+    log::set_boxed_logger(Box::new(Logger {
+        terminal,
+        file: Mutex::new(file),
+    }))?;
+    log::set_max_level(max_level);
+
+    Ok(())
+}
@@ -2,34 +2,127 @@
 #![warn(clippy::nursery)]
 #![allow(clippy::map_err_ignore)]
 
-use std::sync::Arc;
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, Mutex, PoisonError},
+};
 
 use anyhow::Result;
+use chrono::Utc;
 use log::{error, info, warn};
-use settings::Platform;
-use togglebot::{discord, handler, settings, twitch, Response};
-use tokio::sync::{broadcast, mpsc, RwLock};
+use settings::{Config, DataDir, Platform, SharedState};
+use structopt::StructOpt;
+use togglebot::{
+    announce::{self, SystemClock},
+    backup::{self, Format},
+    commands::playground::Cooldowns,
+    discord,
+    handler::{self, ErrorReplies},
+    http::CachingClient,
+    logging,
+    moderation::Moderator,
+    settings,
+    timers::{self, Activity, Timers},
+    twitch, CommandInfo, CommandList, Message, OneshotSender, Outboxes, Outgoing, Response, Source,
+};
+use tokio::sync::{broadcast, mpsc, watch};
+
+#[derive(StructOpt)]
+#[structopt(about, author)]
+struct Opt {
+    /// Location of the config file. If not set, `/app/config.toml` and `config.toml` are tried.
+    #[structopt(short, long, env = "TOGGLEBOT_CONFIG", parse(from_os_str))]
+    config: Option<PathBuf>,
+    /// Directory for all data like the state, backups and logs. Each bot instance needs its own.
+    #[structopt(
+        short,
+        long,
+        env = "TOGGLEBOT_DATA_DIR",
+        default_value = ".",
+        parse(from_os_str)
+    )]
+    data_dir: PathBuf,
+    #[structopt(subcommand)]
+    cmd: Option<Cmd>,
+}
+
+/// Maintenance tasks that run instead of the bot. The bot should not be running for the same data
+/// directory at the same time.
+#[derive(StructOpt)]
+enum Cmd {
+    /// Export the whole state as a bundle.
+    Export {
+        /// Format of the bundle, either `json` or `toml`.
+        #[structopt(short, long, default_value = "json")]
+        format: Format,
+        /// File to write the bundle to. If not set, it's printed to stdout.
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+    /// Merge a previously exported bundle into the current state.
+    Import {
+        /// The bundle to import. Its format is detected from the file extension.
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+        /// Only show the changes, without saving them.
+        #[structopt(long)]
+        dry_run: bool,
+    },
+}
+
+async fn run_command(cmd: Cmd, state: &SharedState) -> Result<()> {
+    match cmd {
+        Cmd::Export { format, output } => {
+            let bundle = backup::export(&*state.read().await, format, Utc::now())?;
+            match output {
+                Some(path) => std::fs::write(path, bundle)?,
+                None => println!("{}", bundle),
+            }
+        }
+        Cmd::Import { file, dry_run } => {
+            let incoming = backup::read(&file)?;
+            let changes = backup::merge(&mut *state.write().await, incoming, dry_run)?;
+
+            for change in &changes {
+                println!("{}", change);
+            }
+            if changes.is_empty() {
+                println!("nothing to change");
+            } else if !dry_run {
+                // Wait for the state to be written, before the process exits.
+                state.save().await?;
+            }
+        }
+    }
+
+    Ok(())
+}
 
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> Result<()> {
     std::env::set_var("RUST_LOG", "warn,togglebot=trace");
-    env_logger::init();
+    let opt = Opt::from_args();
+    let config = settings::load_config(opt.config.as_deref()).await?;
+    let dirs = DataDir::new(opt.data_dir)?;
+    logging::init(&dirs)?;
+    let store = settings::open_store(config.storage, &dirs)?;
+    let state = Arc::new(SharedState::load(store)?);
 
-    let config = settings::load_config().await?;
-    println!("{:?}", config);
-    let state = settings::load_state().await?;
-    let state = Arc::new(RwLock::new(state));
+    if let Some(cmd) = opt.cmd {
+        return run_command(cmd, &state).await;
+    }
 
-    let (shutdown_tx, shutdown_rx) = broadcast::channel(1);
-    let shutdown_rx2 = shutdown_tx.subscribe();
+    let (shutdown_tx, _) = broadcast::channel(1);
+    let shutdown = shutdown_tx.clone();
 
     let cloned = state.clone();
     tokio::spawn(async move {
         tokio::signal::ctrl_c().await.ok();
 
         info!("bot shutting down");
-        let state = cloned.read().await;
-        settings::save_state(&state)
+        cloned
+            .save()
             .await
             .map_err(|e| error!("Unable to save state: {}", e))
             .ok();
@@ -37,30 +130,126 @@ async fn main() -> Result<()> {
     });
 
     let (queue_tx, mut queue_rx) = mpsc::channel(100);
+    let (commands_tx, commands_rx) =
+        watch::channel(handler::command_list(&config, &state, Source::Discord).await);
+    let mut outboxes = HashMap::new();
 
-    // if let Some(discord) = &config.discord {
-    //     discord::start(discord, queue_tx.clone(), shutdown_rx).await?;
-    // }
-    // if let Some(twitch) = &config.twitch {
-    //     twitch::start(twitch, queue_tx, shutdown_rx2).await?;
-    // }
+    for (name, platform) in &config.platforms {
+        let (outbox_tx, outbox_rx) = mpsc::channel(10);
+        outboxes.insert(name.clone(), outbox_tx);
 
-    for (name, platform) in config.platforms.iter() {
         match platform {
-            Platform::Discord(_) => todo!(),
-            Platform::Twitch(_) => todo!(),
+            Platform::Discord(discord) => {
+                discord::start(
+                    name.clone(),
+                    discord,
+                    queue_tx.clone(),
+                    outbox_rx,
+                    commands_rx.clone(),
+                    state.clone(),
+                    shutdown.subscribe(),
+                )
+                .await?;
+            }
+            Platform::Twitch(twitch) => {
+                twitch::start(
+                    name.clone(),
+                    twitch,
+                    config.moderation.is_some(),
+                    queue_tx.clone(),
+                    outbox_rx,
+                    shutdown.subscribe(),
+                )
+                .await?;
+            }
         }
     }
 
+    drop(queue_tx);
+    let outboxes = Arc::new(outboxes);
+
+    if let Some(announcements) = config.announcements.clone() {
+        tokio::spawn(announce::run(
+            announcements,
+            state.clone(),
+            outboxes.clone(),
+            SystemClock,
+            shutdown.subscribe(),
+        ));
+    }
+
+    if config.backups.keep > 0 {
+        tokio::spawn(backup::run(
+            config.backups,
+            state.clone(),
+            dirs.backups()?,
+            SystemClock,
+            shutdown.subscribe(),
+        ));
+    }
+
+    let activity = Arc::new(Activity::new(&config));
+
+    if !config.timers.is_empty() {
+        tokio::spawn(timers::run(
+            Timers::new(&config, &activity, Utc::now()),
+            state.clone(),
+            activity.clone(),
+            outboxes.clone(),
+            SystemClock,
+            shutdown.subscribe(),
+        ));
+    }
+
+    let config = Arc::new(config);
+    let http = Arc::new(CachingClient::new()?);
+    let error_replies = Arc::new(Mutex::new(ErrorReplies::default()));
+    let play_cooldowns = Arc::new(Cooldowns::default());
+    let mut moderator = Moderator::default();
+
     while let Some((message, reply)) = queue_rx.recv().await {
+        activity.record(&message.platform);
+
+        if let Some(moderation) = &config.moderation {
+            if let Some(violation) = moderator.check(moderation, &message, Utc::now()) {
+                info!(
+                    "removing message by {} on {}, because it {}",
+                    message.author,
+                    message.source.as_ref(),
+                    violation.reason
+                );
+                if let Some(target) = &moderation.log {
+                    log_violation(&outboxes, target, violation.log(&message)).await;
+                }
+                reply.send(Response::Moderation(violation)).ok();
+                continue;
+            }
+        }
+
         let res = if message.admin {
-            handler::admin_message(state.clone(), message.content)
+            let res = handler::admin_message(&config, state.clone(), &dirs, message.content)
                 .await
-                .map(Response::Admin)
+                .map(Response::Admin);
+            update_commands(&config, &state, &commands_tx, &commands_rx).await;
+            res
+        } else if let Some(resp) =
+            handler::moderator_message(&config, state.clone(), &message).await
+        {
+            update_commands(&config, &state, &commands_tx, &commands_rx).await;
+            Ok(Response::Admin(resp))
         } else {
-            handler::user_message(&config, state.clone(), message)
-                .await
-                .map(Response::User)
+            // User commands might wait for slow requests like running code in the playground, so
+            // they run in their own task to not hold up the following messages.
+            tokio::spawn(user_message(
+                config.clone(),
+                state.clone(),
+                http.clone(),
+                play_cooldowns.clone(),
+                error_replies.clone(),
+                message,
+                reply,
+            ));
+            continue;
         };
 
         match res {
@@ -75,3 +264,56 @@ async fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// Handle a message from a regular user and send back the response.
+async fn user_message(
+    config: Arc<Config>,
+    state: Arc<SharedState>,
+    http: Arc<CachingClient>,
+    cooldowns: Arc<Cooldowns>,
+    error_replies: Arc<Mutex<ErrorReplies>>,
+    message: Message,
+    reply: OneshotSender<Response>,
+) {
+    let source = message.source;
+    match handler::user_message(&config, state, &*http, &cooldowns, message).await {
+        Ok(resp) => {
+            let resp = error_replies
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .limit(source, resp, Utc::now());
+            reply.send(Response::User(resp)).ok();
+        }
+        Err(e) => {
+            error!("error during event handling: {}", e);
+        }
+    }
+}
+
+/// Post a note about a removed message to the moderation log.
+async fn log_violation(outboxes: &Outboxes, target: &settings::Target, content: String) {
+    if let Some(outbox) = outboxes.get(&target.platform) {
+        let message = Outgoing {
+            channel: target.channel,
+            content,
+        };
+        if outbox.send(message).await.is_err() {
+            warn!("platform `{}` is not running anymore", target.platform);
+        }
+    } else {
+        warn!("unknown moderation log platform `{}`", target.platform);
+    }
+}
+
+/// Publish the list of commands again, if an admin or moderator changed any of them.
+async fn update_commands(
+    config: &settings::Config,
+    state: &Arc<SharedState>,
+    tx: &watch::Sender<Vec<CommandInfo>>,
+    rx: &CommandList,
+) {
+    let list = handler::command_list(config, state, Source::Discord).await;
+    if *rx.borrow() != list {
+        tx.send(list).ok();
+    }
+}
@@ -0,0 +1,296 @@
+//! Spam protection, that checks every message against the moderation rules before it's handled
+//! like a command.
+
+use std::collections::{HashMap, VecDeque};
+
+use chrono::{DateTime, Duration, Utc};
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::{
+    settings::{Moderation, ModerationAction},
+    Message, Source,
+};
+
+/// A message that broke one of the moderation rules, together with what should happen about it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Violation {
+    /// Short explanation of the broken rule, like "contains a Discord invite".
+    pub reason: &'static str,
+    pub actions: Vec<ModerationAction>,
+    /// Seconds that the user is timed out for, if that's one of the actions.
+    pub timeout: u32,
+    warning: String,
+}
+
+impl Violation {
+    /// Whether the given action should be taken against the message.
+    #[must_use]
+    pub fn has(&self, action: ModerationAction) -> bool {
+        self.actions.contains(&action)
+    }
+
+    /// The warning for the user, addressed with the given mention.
+    #[must_use]
+    pub fn warning(&self, user: &str) -> String {
+        self.warning
+            .replace("{user}", user)
+            .replace("{reason}", self.reason)
+    }
+
+    /// Note for the moderation log about the removed message.
+    #[must_use]
+    pub fn log(&self, message: &Message) -> String {
+        format!(
+            "Removed a message by {} on {}, because it {}: {}",
+            message.author,
+            message.source.as_ref(),
+            self.reason,
+            message.content
+        )
+    }
+}
+
+/// Checks messages against the moderation rules and keeps the recent messages of every user, to
+/// notice repeated ones.
+#[derive(Default)]
+pub struct Moderator {
+    recent: HashMap<(Source, String), VecDeque<(String, DateTime<Utc>)>>,
+}
+
+impl Moderator {
+    /// Check the message against all rules, returning the first one it broke. Admins and
+    /// moderators are never affected.
+    pub fn check(
+        &mut self,
+        config: &Moderation,
+        message: &Message,
+        now: DateTime<Utc>,
+    ) -> Option<Violation> {
+        if message.admin || message.moderator {
+            return None;
+        }
+
+        let repeats = self.record(config, message, now);
+
+        let reason = if blocked_domain(&config.blocked_domains, &message.content) {
+            "links to a blocked site"
+        } else if config.block_invites && has_invite(&config.allowed_invites, &message.content) {
+            "contains a Discord invite"
+        } else if config.max_mentions.map_or(false, |max| {
+            mentions(message.source, &message.content) > max
+        }) {
+            "mentions too many users"
+        } else if config.max_repeats.map_or(false, |max| repeats > max) {
+            "repeats the same message too often"
+        } else {
+            return None;
+        };
+
+        Some(Violation {
+            reason,
+            actions: config.actions.clone(),
+            timeout: config.timeout,
+            warning: config.warning.clone(),
+        })
+    }
+
+    /// Remember the message and return how often the user sent it within the repeat window,
+    /// including this time.
+    fn record(&mut self, config: &Moderation, message: &Message, now: DateTime<Utc>) -> usize {
+        if config.max_repeats.is_none() {
+            return 0;
+        }
+
+        let since = now - Duration::seconds(config.repeat_window.into());
+        self.recent.retain(|_, history| {
+            while history.front().map_or(false, |(_, time)| *time <= since) {
+                history.pop_front();
+            }
+            !history.is_empty()
+        });
+
+        let content = normalize(&message.content);
+        let history = self
+            .recent
+            .entry((message.source, message.author.clone()))
+            .or_default();
+        history.push_back((content.clone(), now));
+
+        history.iter().filter(|(c, _)| *c == content).count()
+    }
+}
+
+/// Whether the content links to any of the blocked domains or their subdomains.
+fn blocked_domain(blocked: &[String], content: &str) -> bool {
+    lazy_static! {
+        static ref DOMAIN: Regex =
+            Regex::new(r"(?i)\b((?:[a-z0-9-]+\.)+[a-z]{2,})\b").expect("domain regex to be valid");
+    }
+
+    if blocked.is_empty() {
+        return false;
+    }
+
+    DOMAIN.captures_iter(content).any(|captures| {
+        let domain = captures[1].to_lowercase();
+        blocked.iter().any(|blocked| {
+            let blocked = blocked.to_lowercase();
+            domain == blocked || domain.ends_with(&format!(".{}", blocked))
+        })
+    })
+}
+
+/// Whether the content contains a Discord invite, that isn't one of the allowed ones.
+fn has_invite(allowed: &[String], content: &str) -> bool {
+    lazy_static! {
+        static ref INVITE: Regex =
+            Regex::new(r"(?i:discord(?:app)?\.com/invite|discord\.gg)/([a-zA-Z0-9-]+)")
+                .expect("invite regex to be valid");
+    }
+
+    INVITE
+        .captures_iter(content)
+        .any(|captures| !allowed.iter().any(|code| *code == captures[1]))
+}
+
+/// Number of users, roles or groups mentioned in the content.
+fn mentions(source: Source, content: &str) -> usize {
+    lazy_static! {
+        static ref DISCORD: Regex =
+            Regex::new(r"<@[!&]?\d+>|@everyone|@here").expect("Discord mention regex to be valid");
+        static ref TWITCH: Regex = Regex::new(r"@\w+").expect("Twitch mention regex to be valid");
+    }
+
+    match source {
+        Source::Discord => DISCORD.find_iter(content).count(),
+        Source::Twitch => TWITCH.find_iter(content).count(),
+    }
+}
+
+/// Make small changes like casing or extra spaces not count as a different message.
+fn normalize(content: &str) -> String {
+    content
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn config() -> Moderation {
+        toml::from_str(
+            r#"
+            blocked_domains = ["spam.com"]
+            block_invites = true
+            allowed_invites = ["qtyDMat"]
+            max_mentions = 2
+            max_repeats = 2
+            "#,
+        )
+        .unwrap()
+    }
+
+    fn message(source: Source, content: &str) -> Message {
+        Message {
+            source,
+            platform: source.as_ref().to_lowercase(),
+            content: content.to_owned(),
+            admin: false,
+            moderator: false,
+            author: "user".to_owned(),
+        }
+    }
+
+    #[test]
+    fn rules() {
+        let config = config();
+        let now = Utc.ymd(2026, 10, 18).and_hms(12, 0, 0);
+        let mut moderator = Moderator::default();
+        let mut check = |source, content| {
+            moderator
+                .check(&config, &message(source, content), now)
+                .map(|violation| violation.reason)
+        };
+
+        assert_eq!(None, check(Source::Discord, "see https://docs.rs/tokio"));
+        assert_eq!(
+            Some("links to a blocked site"),
+            check(Source::Twitch, "free stuff at www.SPAM.com/win")
+        );
+        assert_eq!(None, check(Source::Discord, "notspam.com is fine"));
+        assert_eq!(
+            None,
+            check(Source::Discord, "join https://discord.gg/qtyDMat")
+        );
+        assert_eq!(
+            Some("contains a Discord invite"),
+            check(Source::Discord, "join discord.com/invite/abc123")
+        );
+        assert_eq!(None, check(Source::Discord, "<@1> <@!2> hi"));
+        assert_eq!(
+            Some("mentions too many users"),
+            check(Source::Discord, "<@1> <@!2> <@&3> hi")
+        );
+        assert_eq!(
+            Some("mentions too many users"),
+            check(Source::Twitch, "@a @b @c hi")
+        );
+    }
+
+    #[test]
+    fn repeats() {
+        let config = config();
+        let start = Utc.ymd(2026, 10, 18).and_hms(12, 0, 0);
+        let mut moderator = Moderator::default();
+        let mut check = |content, seconds| {
+            moderator
+                .check(
+                    &config,
+                    &message(Source::Twitch, content),
+                    start + Duration::seconds(seconds),
+                )
+                .map(|violation| violation.reason)
+        };
+
+        assert_eq!(None, check("hello", 0));
+        assert_eq!(None, check("HELLO ", 10));
+        assert_eq!(
+            Some("repeats the same message too often"),
+            check("hello", 20)
+        );
+        assert_eq!(None, check("hello", 75));
+    }
+
+    #[test]
+    fn exempt_and_warning() {
+        let config = config();
+        let mut admin = message(Source::Discord, "discord.gg/abc");
+        admin.moderator = true;
+
+        assert_eq!(
+            None,
+            Moderator::default().check(&config, &admin, Utc::now())
+        );
+
+        let violation = Moderator::default()
+            .check(
+                &config,
+                &message(Source::Discord, "discord.gg/abc"),
+                Utc::now(),
+            )
+            .unwrap();
+        assert!(violation.has(ModerationAction::Delete));
+        assert!(!violation.has(ModerationAction::Timeout));
+        assert_eq!(
+            "<@1> your message was removed, because it contains a Discord invite",
+            violation.warning("<@1>")
+        );
+    }
+}
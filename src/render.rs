@@ -0,0 +1,198 @@
+//! Layout of responses within the message length limits of each platform.
+
+use crate::settings::Embed;
+
+/// Maximum length of a single Twitch message, in characters.
+pub const TWITCH_LIMIT: usize = 500;
+/// Maximum length of a single Discord message, in characters.
+pub const DISCORD_LIMIT: usize = 2000;
+
+/// Split the text into parts of at most `limit` characters each. Parts are broken after
+/// paragraphs if possible, then after lines, then between words and only as last resort in the
+/// middle of a word.
+#[must_use]
+pub fn split(text: &str, limit: usize) -> Vec<String> {
+    let limit = limit.max(1);
+    let mut parts = Vec::new();
+    let mut rest = text.trim();
+
+    while let Some((end, _)) = rest.char_indices().nth(limit) {
+        let window = &rest[..end];
+        // Breaking early is only worth it if the part doesn't become too short.
+        let pos = ["\n\n", "\n", " "]
+            .iter()
+            .find_map(|sep| window.rfind(sep).filter(|pos| *pos > window.len() / 2))
+            .unwrap_or(end);
+
+        parts.push(rest[..pos].trim_end().to_owned());
+        rest = rest[pos..].trim_start();
+    }
+
+    if !rest.is_empty() {
+        parts.push(rest.to_owned());
+    }
+
+    parts
+}
+
+/// Group the items into pages, where the items of each page joined with the separator are at
+/// most `limit` characters long. Items that are too long by themselves get a page of their own.
+#[must_use]
+pub fn paginate(items: &[String], separator: &str, limit: usize) -> Vec<String> {
+    let separator_len = separator.chars().count();
+    let mut pages = Vec::new();
+    let mut page = String::new();
+    let mut page_len = 0;
+
+    for item in items {
+        let item_len = item.chars().count();
+
+        if !page.is_empty() && page_len + separator_len + item_len > limit {
+            pages.push(std::mem::take(&mut page));
+            page_len = 0;
+        }
+        if !page.is_empty() {
+            page.push_str(separator);
+            page_len += separator_len;
+        }
+
+        page.push_str(item);
+        page_len += item_len;
+    }
+
+    if !page.is_empty() {
+        pages.push(page);
+    }
+
+    pages
+}
+
+/// Get a single page of the items as paginated by [`paginate`], where the first page has the
+/// number 1. Returns the page if it exists, together with the total count of pages, which is at
+/// least 1 so that an empty list still has a first page.
+#[must_use]
+pub fn page(
+    items: &[String],
+    separator: &str,
+    limit: usize,
+    number: usize,
+) -> (Option<String>, usize) {
+    let mut pages = paginate(items, separator, limit);
+    let total = pages.len().max(1);
+
+    let page = match number {
+        1 if pages.is_empty() => Some(String::new()),
+        n if (1..=pages.len()).contains(&n) => Some(pages.swap_remove(n - 1)),
+        _ => None,
+    };
+
+    (page, total)
+}
+
+/// Cut the text down to at most `limit` characters, marking the cut with an ellipsis.
+#[must_use]
+pub fn truncate(text: &str, limit: usize) -> String {
+    match text.char_indices().nth(limit.saturating_sub(1)) {
+        Some((pos, _)) if text[pos..].chars().nth(1).is_some() => format!("{}…", &text[..pos]),
+        _ => text.to_owned(),
+    }
+}
+
+/// Turn an embed into a single line of plain text, for platforms that don't support embeds. The
+/// thumbnail and color are left out.
+#[must_use]
+pub fn flatten(embed: &Embed) -> String {
+    let fields = embed
+        .fields
+        .iter()
+        .map(|field| format!("{}: {}", field.name, field.value));
+
+    embed
+        .title
+        .iter()
+        .chain(&embed.description)
+        .cloned()
+        .chain(fields)
+        .chain(embed.footer.iter().cloned())
+        .chain(embed.url.iter().cloned())
+        .map(|part| part.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::settings::EmbedField;
+
+    #[test]
+    fn split_at_boundaries() {
+        assert_eq!(vec!["short"], split("short", 10));
+        assert!(split("", 10).is_empty());
+
+        assert_eq!(
+            vec!["first paragraph", "second one"],
+            split("first paragraph\n\nsecond one", 20)
+        );
+        assert_eq!(
+            vec!["some words that", "are too long"],
+            split("some words that are too long", 16)
+        );
+        assert_eq!(vec!["abcd", "efgh", "ij"], split("abcdefghij", 4));
+        assert_eq!(vec!["äöü", "ß"], split("äöüß", 3));
+    }
+
+    #[test]
+    fn paginate_items() {
+        let items = ["!a", "!bb", "!ccc", "!dddddddd"]
+            .iter()
+            .map(|s| (*s).to_owned())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            vec!["!a, !bb", "!ccc", "!dddddddd"],
+            paginate(&items, ", ", 8)
+        );
+        assert_eq!(
+            vec!["!a, !bb, !ccc, !dddddddd"],
+            paginate(&items, ", ", 100)
+        );
+        assert!(paginate(&[], ", ", 8).is_empty());
+
+        assert_eq!((Some("!ccc".to_owned()), 3), page(&items, ", ", 8, 2));
+        assert_eq!((None, 3), page(&items, ", ", 8, 4));
+        assert_eq!((Some(String::new()), 1), page(&[], ", ", 8, 1));
+    }
+
+    #[test]
+    fn flatten_embed() {
+        let embed = Embed {
+            title: Some("Rust".to_owned()),
+            description: Some("A language empowering everyone\nto build software".to_owned()),
+            url: Some("https://www.rust-lang.org".to_owned()),
+            fields: vec![EmbedField {
+                name: "Edition".to_owned(),
+                value: "2018".to_owned(),
+                inline: true,
+            }],
+            footer: Some(String::new()),
+            ..Embed::default()
+        };
+
+        assert_eq!(
+            "Rust | A language empowering everyone to build software | Edition: 2018 | \
+             https://www.rust-lang.org",
+            flatten(&embed)
+        );
+        assert_eq!("", flatten(&Embed::default()));
+    }
+
+    #[test]
+    fn truncate_text() {
+        assert_eq!("abc", truncate("abc", 3));
+        assert_eq!("ab…", truncate("abcd", 3));
+    }
+}
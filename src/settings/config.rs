@@ -1,14 +1,51 @@
-use crate::commands::Type;
+use serde::Deserialize;
 
-use super::HashMap;
-use super::config_parsing::{Argument, ConfigDto, Links};
+use super::{
+    config_parsing::{
+        Announcements, Backups, Commands, ConfigDto, Crates, Links, Moderation, Platforms,
+        Playground, Storage, Timer,
+    },
+    Discord, HashMap, Twitch,
+};
+use crate::Source;
+
+#[derive(Debug, Default)]
 pub struct Config {
-    pub platforms: HashMap<String, Platform>
+    pub platforms: HashMap<String, Platform>,
+    pub links: Links,
+    pub commands: Commands,
+    pub announcements: Option<Announcements>,
+    pub timers: HashMap<String, Timer>,
+    pub storage: Storage,
+    pub backups: Backups,
+    pub crates: Crates,
+    pub playground: Playground,
+    pub moderation: Option<Moderation>,
 }
 
-impl From<ConfigDto> for Config{
-    fn from(_: ConfigDto) -> Self {
-        todo!()
+impl From<ConfigDto> for Config {
+    fn from(dto: ConfigDto) -> Self {
+        let platforms = match dto.platforms {
+            Platforms::Standard { discord, twitch } => discord
+                .map(|d| ("discord".to_owned(), Platform::Discord(d)))
+                .into_iter()
+                .chain(twitch.map(|t| ("twitch".to_owned(), Platform::Twitch(t))))
+                .collect(),
+            Platforms::Custom(platforms) => platforms,
+        };
+
+        Self {
+            platforms,
+            links: dto.links,
+            commands: dto.commands,
+            announcements: dto.announcements,
+            timers: dto.timers,
+            storage: dto.storage,
+            backups: dto.backups,
+            crates: dto.crates,
+            playground: dto.playground,
+            moderation: dto.moderation,
+        }
     }
 }
 
@@ -18,16 +55,7 @@ impl IntoIterator for Links {
     type IntoIter = std::collections::hash_map::IntoIter<String, String>;
 
     fn into_iter(self) -> Self::IntoIter {
-        todo!()
-        // self.0.clone().into_iter()
-    }
-}
-
-impl Argument {
-    pub fn get_type(&self) -> &Type {
-        match &self {
-            Argument::Simple(t) | Argument::Test(t) | Argument::Format(t) => t,
-        }
+        self.0.into_iter()
     }
 }
 
@@ -38,3 +66,13 @@ pub enum Platform {
     Twitch(Twitch),
 }
 
+impl Platform {
+    /// The kind of service this platform instance connects to.
+    #[must_use]
+    pub const fn source(&self) -> Source {
+        match self {
+            Self::Discord(_) => Source::Discord,
+            Self::Twitch(_) => Source::Twitch,
+        }
+    }
+}
@@ -1,14 +1,21 @@
 #[cfg(test)]
 use std::{collections::hash_map::DefaultHasher, hash::BuildHasherDefault};
-use std::{env, num::NonZeroU32, str::FromStr};
+use std::{
+    env,
+    fmt::{self, Display},
+    num::NonZeroU32,
+    str::FromStr,
+};
 
+use chrono::Weekday;
 use derivative::Derivative;
 use lazy_static::lazy_static;
 use regex::Regex;
-use serde::Deserialize;
-use serde_with::DeserializeFromStr;
+use serde::{Deserialize, Serialize};
+use serde_with::{DeserializeFromStr, SerializeDisplay};
 
-use crate::{commands::Type, Source};
+use super::config::Platform;
+use crate::{commands::Type, render, Source};
 
 #[cfg(not(test))]
 type HashMap<K, V> = std::collections::HashMap<K, V>;
@@ -17,9 +24,9 @@ type HashMap<K, V> = std::collections::HashMap<K, V, BuildHasherDefault<DefaultH
 
 #[derive(Derivative, Deserialize, Clone)]
 #[derivative(Debug = "transparent", Default)]
-pub struct Links(HashMap<String, String>);
+pub struct Links(pub(super) HashMap<String, String>);
 
-type Commands = HashMap<String, CommandItem>;
+pub type Commands = HashMap<String, CommandItem>;
 
 #[derive(Debug, DeserializeFromStr, Clone)]
 pub struct NamedFunction(String, String);
@@ -30,36 +37,285 @@ pub struct NamedFunction(String, String);
 pub enum CommandItem {
     Function(NamedFunction),
     Message(String),
+    Counter(Counter),
     Custom(Command),
 }
 
+impl CommandItem {
+    /// Whether the given name is either the command's name or one of its aliases, if the command
+    /// supports aliases.
+    #[must_use]
+    pub fn matches(&self, own_name: &str, name: &str) -> bool {
+        match self {
+            Self::Counter(counter) => counter.matches(own_name, name),
+            Self::Custom(command) => command.matches(own_name, name),
+            _ => own_name.eq_ignore_ascii_case(name),
+        }
+    }
+}
+
+/// A command that shows a named counter, like the amount of bugs hit today. Moderators can change
+/// it with `+`, `-` and `reset` as argument.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Counter {
+    /// Name of the counter in the state, so several commands can share the same counter.
+    pub counter: String,
+    /// Response that contains the current value in place of `{count}`.
+    pub format: FormatString,
+    /// Start over at zero whenever a new stream started, according to the schedule.
+    #[serde(default)]
+    pub reset_per_stream: bool,
+    pub aliases: Option<Vec<String>>,
+    #[serde(default = "all_platforms")]
+    pub platforms: Vec<Source>,
+}
+
+impl Counter {
+    /// Whether the given name is either the command's name or one of its aliases.
+    #[must_use]
+    pub fn matches(&self, own_name: &str, name: &str) -> bool {
+        own_name.eq_ignore_ascii_case(name)
+            || self
+                .aliases
+                .iter()
+                .flatten()
+                .any(|alias| alias.eq_ignore_ascii_case(name))
+    }
+}
+
 fn all_platforms() -> Vec<Source> {
     vec![Source::Discord, Source::Twitch]
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(untagged)]
 pub enum FormatString {
     Universal(String),
     Specific(HashMap<Source, String>),
+    /// A Discord embed, that other platforms get as plain text.
+    Embed(Embed),
 }
 
-#[derive(DeserializeFromStr, Clone, Debug)]
-pub enum Argument {
-    Simple(Type),
-    Test(Type),
-    Format(Type),
+/// The format of a response for a single platform.
+#[derive(Clone, Copy, Debug)]
+pub enum Format<'a> {
+    Text(&'a str),
+    Embed(&'a Embed),
 }
 
-#[derive(Derivative, Deserialize, Clone)]
+impl FormatString {
+    /// Get the format for the given source, if there is any.
+    #[must_use]
+    pub fn get(&self, source: Source) -> Option<Format<'_>> {
+        match self {
+            Self::Universal(format) => Some(Format::Text(format)),
+            Self::Specific(map) => map.get(&source).map(|format| Format::Text(format)),
+            Self::Embed(embed) => Some(Format::Embed(embed)),
+        }
+    }
+}
+
+/// Structured response for Discord, where all texts can contain placeholders.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Embed {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Link that the title points to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<Color>,
+    /// Link to a small image in the top right corner.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thumbnail: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub footer: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<EmbedField>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct EmbedField {
+    pub name: String,
+    pub value: String,
+    /// Show the field next to other inline fields, instead of on its own line.
+    #[serde(default)]
+    pub inline: bool,
+}
+
+/// Color of an embed's side bar, written in hex like `#ce422b`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, DeserializeFromStr, SerializeDisplay)]
+pub struct Color(pub u32);
+
+impl FromStr for Color {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.strip_prefix('#')
+            .filter(|hex| hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()))
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .map(Self)
+            .ok_or_else(|| format!("invalid color `{}`, expected hex like `#ce422b`", s))
+    }
+}
+
+impl Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:06x}", self.0)
+    }
+}
+
+/// A single argument of a command, written as
+/// `[name:]type[arity][?|!parse_argument][<!>error_message]`.
+#[derive(DeserializeFromStr, SerializeDisplay, Clone, Debug, PartialEq, Eq)]
+pub struct Argument {
+    /// Name that is shown in the usage of the command, defaulting to the type name.
+    pub name: Option<String>,
+    pub mode: ArgumentMode,
+    pub ty: Type,
+    pub arity: Arity,
+    /// Message that is shown when the value is invalid, where `{}` is replaced with the value.
+    pub error: Option<String>,
+}
+
+/// How the value of an argument is checked and transformed before it's used.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArgumentMode {
+    /// Use the value as is.
+    Simple,
+    /// Check the value with the type's test, written as `?`.
+    Test,
+    /// Replace the value with the type's formatted output, written as `!`.
+    Format,
+}
+
+/// Whether an argument has to be given.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Arity {
+    Required,
+    /// Written as `string?` and left empty when missing.
+    Optional,
+    /// Written as `string=chat`, where the value is used when missing.
+    Default(String),
+    /// Written as `string...` and takes all remaining words, which may be none. Only allowed for
+    /// the last argument.
+    Rest,
+}
+
+#[derive(Derivative, Deserialize, Serialize, Clone)]
 #[derivative(Debug)]
 pub struct Command {
+    #[serde(
+        default,
+        deserialize_with = "deserialize_args",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub args: Option<Vec<Argument>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub format: Option<FormatString>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub cooldown: Option<NonZeroU32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub aliases: Option<Vec<String>>,
     #[serde(default = "all_platforms")]
     pub platforms: Vec<Source>,
+    /// Data to request from a JSON API, before filling in the format.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fetch: Option<Fetch>,
+}
+
+impl Command {
+    /// Create a command without any content, that is available on no platforms yet.
+    #[must_use]
+    pub const fn empty() -> Self {
+        Self {
+            args: None,
+            format: None,
+            cooldown: None,
+            aliases: None,
+            platforms: Vec::new(),
+            fetch: None,
+        }
+    }
+
+    /// Set the content for a single source, or all sources if none is given. Content of other
+    /// sources is kept intact.
+    pub fn set_content(&mut self, source: Option<Source>, content: &str) {
+        if let Some(source) = source {
+            let mut map = self.specific_formats();
+            map.insert(source, content.to_owned());
+            self.format = Some(FormatString::Specific(map));
+
+            if !self.platforms.contains(&source) {
+                self.platforms.push(source);
+            }
+        } else {
+            self.format = Some(FormatString::Universal(content.to_owned()));
+            self.platforms = all_platforms();
+        }
+    }
+
+    /// Remove the content for a single source and make the command unavailable on it.
+    pub fn remove_source(&mut self, source: Source) {
+        let mut map = self.specific_formats();
+        map.remove(&source);
+        self.format = Some(FormatString::Specific(map));
+        self.platforms.retain(|s| *s != source);
+    }
+
+    /// Get the content for each of the command's sources separately.
+    fn specific_formats(&self) -> HashMap<Source, String> {
+        match &self.format {
+            Some(FormatString::Universal(content)) => self
+                .platforms
+                .iter()
+                .map(|source| (*source, content.clone()))
+                .collect(),
+            Some(FormatString::Specific(map)) => map.clone(),
+            // Editing a single platform turns the embed into plain text for all of them.
+            Some(FormatString::Embed(embed)) => self
+                .platforms
+                .iter()
+                .map(|source| (*source, render::flatten(embed)))
+                .collect(),
+            None => HashMap::default(),
+        }
+    }
+
+    /// Whether the given name is either the command's name or one of its aliases.
+    #[must_use]
+    pub fn matches(&self, own_name: &str, name: &str) -> bool {
+        own_name.eq_ignore_ascii_case(name)
+            || self
+                .aliases
+                .iter()
+                .flatten()
+                .any(|alias| alias.eq_ignore_ascii_case(name))
+    }
+}
+
+/// A request to a JSON API, whose response is available as named values in the format of a
+/// command.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Fetch {
+    /// Link to request, which is a template like the format with URL encoded arguments.
+    pub url: String,
+    /// Values to extract from the response by their name, as paths like `$.crate.max_version`.
+    pub fields: HashMap<String, String>,
+    /// Message that is shown when the request fails, as template with the same arguments.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Seconds to cache responses for.
+    #[serde(default = "default_fetch_cache")]
+    pub cache: u32,
+}
+
+const fn default_fetch_cache() -> u32 {
+    5 * 60
 }
 
 #[derive(Debug, Deserialize)]
@@ -74,10 +330,255 @@ pub enum Platforms {
 
 #[derive(Deserialize, Debug)]
 pub struct ConfigDto {
-    platforms: Platforms,
+    pub platforms: Platforms,
     #[serde(default)]
     pub links: Links,
     pub commands: Commands,
+    pub announcements: Option<Announcements>,
+    #[serde(default)]
+    pub timers: HashMap<String, Timer>,
+    #[serde(default)]
+    pub storage: Storage,
+    #[serde(default)]
+    pub backups: Backups,
+    #[serde(default)]
+    pub crates: Crates,
+    #[serde(default)]
+    pub playground: Playground,
+    pub moderation: Option<Moderation>,
+}
+
+/// Backend that is used to persist the bot's state.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Storage {
+    /// A single `state.json` file.
+    Json,
+    /// An embedded SQLite database in `state.db`.
+    Sqlite,
+}
+
+impl Default for Storage {
+    fn default() -> Self {
+        Self::Json
+    }
+}
+
+/// Settings for the periodic backups of the state.
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct Backups {
+    /// Hours between two backups.
+    #[serde(default = "default_backup_interval")]
+    pub interval: NonZeroU32,
+    /// Amount of backups to keep, older ones are deleted. Backups are disabled if set to `0`.
+    #[serde(default = "default_backup_keep")]
+    pub keep: usize,
+}
+
+impl Default for Backups {
+    fn default() -> Self {
+        Self {
+            interval: default_backup_interval(),
+            keep: default_backup_keep(),
+        }
+    }
+}
+
+fn default_backup_interval() -> NonZeroU32 {
+    NonZeroU32::new(24).unwrap()
+}
+
+const fn default_backup_keep() -> usize {
+    7
+}
+
+/// Settings for the built-in `!crate` lookup.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Crates {
+    /// Base URL of a crates.io compatible registry API.
+    #[serde(default = "default_crates_api")]
+    pub api: String,
+    /// Base URL of the documentation host, where docs are found at `<docs>/<crate>/<version>`.
+    #[serde(default = "default_crates_docs")]
+    pub docs: String,
+}
+
+impl Default for Crates {
+    fn default() -> Self {
+        Self {
+            api: default_crates_api(),
+            docs: default_crates_docs(),
+        }
+    }
+}
+
+fn default_crates_api() -> String {
+    "https://crates.io/api/v1".to_owned()
+}
+
+fn default_crates_docs() -> String {
+    "https://docs.rs".to_owned()
+}
+
+/// Settings for running code snippets with `!play` and `!eval`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Playground {
+    /// Endpoint that executes code, compatible with the `/execute` API of the Rust playground.
+    #[serde(default = "default_playground_url")]
+    pub url: String,
+    /// Rust edition to compile the code with.
+    #[serde(default = "default_playground_edition")]
+    pub edition: String,
+    /// Time in seconds before the same user can run code again.
+    #[serde(default = "default_playground_cooldown")]
+    pub cooldown: u32,
+    /// Maximum size of the code in bytes.
+    #[serde(default = "default_playground_max_code")]
+    pub max_code: usize,
+    /// Maximum number of characters of the output that is shown.
+    #[serde(default = "default_playground_max_output")]
+    pub max_output: usize,
+}
+
+impl Default for Playground {
+    fn default() -> Self {
+        Self {
+            url: default_playground_url(),
+            edition: default_playground_edition(),
+            cooldown: default_playground_cooldown(),
+            max_code: default_playground_max_code(),
+            max_output: default_playground_max_output(),
+        }
+    }
+}
+
+fn default_playground_url() -> String {
+    "https://play.rust-lang.org/execute".to_owned()
+}
+
+fn default_playground_edition() -> String {
+    "2018".to_owned()
+}
+
+const fn default_playground_cooldown() -> u32 {
+    30
+}
+
+const fn default_playground_max_code() -> usize {
+    2000
+}
+
+const fn default_playground_max_output() -> usize {
+    400
+}
+
+/// Rules against spam, that apply to the messages of all platforms. Admins and moderators are
+/// exempt from them.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Moderation {
+    /// Links to these domains or any of their subdomains are removed.
+    #[serde(default)]
+    pub blocked_domains: Vec<String>,
+    /// Remove Discord invite links, except the ones in `allowed_invites`.
+    #[serde(default)]
+    pub block_invites: bool,
+    /// Invite codes that may be posted, like `qtyDMat` for `https://discord.gg/qtyDMat`.
+    #[serde(default)]
+    pub allowed_invites: Vec<String>,
+    /// Maximum number of mentions in a single message.
+    #[serde(default)]
+    pub max_mentions: Option<usize>,
+    /// How often a user may send the same message within the repeat window.
+    #[serde(default)]
+    pub max_repeats: Option<usize>,
+    /// Seconds in which repeated messages are counted.
+    #[serde(default = "default_repeat_window")]
+    pub repeat_window: u32,
+    /// What happens with a message that breaks a rule.
+    #[serde(default = "default_moderation_actions")]
+    pub actions: Vec<ModerationAction>,
+    /// Seconds that a user is timed out for.
+    #[serde(default = "default_moderation_timeout")]
+    pub timeout: u32,
+    /// Warning for the user, where `{user}` is replaced with a mention of the user and `{reason}`
+    /// with the broken rule.
+    #[serde(default = "default_moderation_warning")]
+    pub warning: String,
+    /// Where a note about every removed message is posted.
+    #[serde(default)]
+    pub log: Option<Target>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModerationAction {
+    /// Delete the message.
+    Delete,
+    /// Reply to the user with the configured warning.
+    Warn,
+    /// Keep the user from writing for a while.
+    Timeout,
+}
+
+const fn default_repeat_window() -> u32 {
+    60
+}
+
+fn default_moderation_actions() -> Vec<ModerationAction> {
+    vec![ModerationAction::Delete, ModerationAction::Warn]
+}
+
+const fn default_moderation_timeout() -> u32 {
+    10 * 60
+}
+
+fn default_moderation_warning() -> String {
+    "{user} your message was removed, because it {reason}".to_owned()
+}
+
+/// Settings for announcements that are posted shortly before a scheduled stream starts.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Announcements {
+    /// How many minutes before the stream starts the announcement is posted.
+    #[serde(default = "default_minutes_before")]
+    pub minutes_before: u32,
+    /// The announcement text, where `{title}`, `{start}` and `{minutes}` are replaced with the
+    /// stream's title, start time and the minutes until the stream starts.
+    pub message: String,
+    /// Title used for days that don't have a more specific one.
+    #[serde(default)]
+    pub title: Option<String>,
+    /// Titles for specific weekdays.
+    #[serde(default)]
+    pub titles: HashMap<Weekday, String>,
+    /// Platforms to post the announcement to.
+    pub targets: Vec<Target>,
+}
+
+const fn default_minutes_before() -> u32 {
+    15
+}
+
+/// A single platform instance (as named in the `platforms` settings) to post messages to.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Target {
+    pub platform: String,
+    /// Channel ID for platforms that serve several channels, like Discord.
+    pub channel: Option<u64>,
+}
+
+/// A message that is posted repeatedly, as long as there is enough activity in the chat.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Timer {
+    pub message: FormatString,
+    /// Minutes between two posts.
+    pub interval: NonZeroU32,
+    /// Minimum amount of chat messages since the last post, so an idle chat isn't flooded with
+    /// the same message over and over.
+    #[serde(default)]
+    pub min_lines: u32,
+    /// Platforms to post the message to.
+    pub targets: Vec<Target>,
 }
 
 pub fn env_token() -> String {
@@ -89,6 +590,10 @@ pub fn env_token() -> String {
 pub struct Discord {
     #[derivative(Debug = "ignore")]
     pub token: String,
+    /// Server to register the slash commands for. Changes to server commands show up instantly,
+    /// while global ones, used if this isn't set, can take up to an hour.
+    #[serde(default)]
+    pub guild: Option<u64>,
 }
 
 #[derive(Clone, Deserialize, Derivative)]
@@ -108,50 +613,178 @@ impl FromStr for Argument {
         lazy_static! {
             static ref RE: Regex = Regex::new(
                 r"(?x)^
-                    (?P<type>[a-zA-Z].*?)
+                    (?:(?P<name>[a-zA-Z_][a-zA-Z0-9_]*):)?
+                    (?P<type>[a-zA-Z]+)
+                    (?:\((?P<params>[^)]*)\))?
+                    (?P<count>[0-9]+)?
+                    (?P<arity>\.+|\?|=[^?!<]*)?
                     (?:
                      (?P<seperator>[?!])
                      (?P<parsearg>.*?)
-                     (?:
-                      (:?<!>)
-                      (?P<errorarg>.*)
-                     )?
+                    )?
+                    (?:
+                     <!>
+                     (?P<errorarg>.*)
                     )?
                     $"
             )
             .expect("I should be ablet to write valid Regex");
         }
 
-        let captures = RE.captures(s).expect("Always matches at least a type");
-        println!("{:?}", captures);
+        let captures = RE
+            .captures(s)
+            .ok_or_else(|| format!("invalid argument `{}`", s))?;
         let type_name = captures
             .name("type")
             .expect("Always matches the beginning of the string.")
             .as_str();
-        let seperator = if let Some(seperator) = captures.name("seperator") {
-            Some(seperator.as_str())
-        } else {
-            None
-        };
-        let parsearg = if let Some(parsearg) = captures.name("parsearg") {
-            Some(parsearg.as_str())
-        } else {
-            None
-        };
-        let errorarg = if let Some(errorarg) = captures.name("errorarg") {
-            Some(errorarg.as_str())
-        } else {
-            None
+        let name = captures.name("name").map(|m| m.as_str().to_owned());
+        let params = captures.name("params").map(|m| m.as_str());
+        let seperator = captures.name("seperator").map(|m| m.as_str());
+        let parsearg = captures.name("parsearg").map(|m| m.as_str());
+        let error = captures.name("errorarg").map(|m| m.as_str().to_owned());
+
+        let mut count = captures
+            .name("count")
+            .map(|m| m.as_str().parse::<usize>())
+            .transpose()
+            .map_err(|e| format!("invalid word count in `{}`: {}", s, e))?;
+
+        let arity = match captures.name("arity").map(|m| m.as_str()) {
+            None => Arity::Required,
+            Some("...") => Arity::Rest,
+            Some("?") => Arity::Optional,
+            Some(dots) if dots.starts_with('.') => {
+                // Older configs repeat dots to take several words, like `string..` for two.
+                if count.is_some() {
+                    return Err(format!("`{}` gives the word count twice", s));
+                }
+                count = Some(dots.len());
+                Arity::Required
+            }
+            Some("=") => return Err(format!("the default value in `{}` is empty", s)),
+            Some(default) => Arity::Default(default[1..].to_owned()),
         };
-        Ok(match (seperator, parsearg, errorarg) {
-            (None, _, _) => Argument::Simple(Type::parse(type_name, None, None)),
-            (Some("!"), a, e) => Argument::Format(Type::parse(type_name, a, e)),
-            (Some("?"), a, e) => Argument::Test(Type::parse(type_name, a, e)),
+
+        let mode = match seperator {
+            None => ArgumentMode::Simple,
+            Some("?") => ArgumentMode::Test,
+            Some("!") => ArgumentMode::Format,
             _ => unreachable!("You found the secret ending"),
+        };
+        let ty = Type::parse(type_name, params, count, parsearg)?;
+
+        if arity == Arity::Rest && !matches!(ty, Type::String(1)) {
+            return Err(format!(
+                "only a plain `string` can take the remaining words, but got `{}`",
+                s
+            ));
+        }
+
+        Ok(Self {
+            name,
+            mode,
+            ty,
+            arity,
+            error,
         })
     }
 }
 
+impl Display for Argument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(name) = &self.name {
+            write!(f, "{}:", name)?;
+        }
+
+        write!(f, "{}", self.ty)?;
+
+        match &self.arity {
+            Arity::Required => {}
+            Arity::Optional => f.write_str("?")?,
+            Arity::Default(value) => write!(f, "={}", value)?,
+            Arity::Rest => f.write_str("...")?,
+        }
+
+        match (self.mode, &self.ty) {
+            // Strings always pass the test and format as themselves, so the mode isn't written.
+            (ArgumentMode::Simple, _) | (_, Type::String(_)) => {}
+            (mode, ty) => {
+                f.write_str(if mode == ArgumentMode::Test { "?" } else { "!" })?;
+                if let Type::Url(Some(pattern)) = ty {
+                    f.write_str(pattern)?;
+                }
+            }
+        }
+
+        if let Some(error) = &self.error {
+            write!(f, "<!>{}", error)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Argument {
+    /// Describe the argument for the usage of a command, like `<name>` for required and `[name]`
+    /// for optional arguments.
+    #[must_use]
+    pub fn label(&self) -> String {
+        let name = self.name.as_deref().unwrap_or_else(|| self.ty.name());
+        let name = match self.ty {
+            Type::String(count) if count > 1 => vec![name; count].join(" "),
+            _ => name.to_owned(),
+        };
+
+        match &self.arity {
+            Arity::Required => format!("<{}>", name),
+            Arity::Optional => format!("[{}]", name),
+            Arity::Default(value) => format!("[{}={}]", name, value),
+            Arity::Rest => format!("[{}...]", name),
+        }
+    }
+}
+
+/// Check that the arguments of a command can be told apart by position. Optional arguments can
+/// only be followed by other optional ones and the remaining words can only be taken by the last
+/// argument.
+pub fn validate_args(args: &[Argument]) -> Result<(), String> {
+    let mut optional = None;
+
+    for (i, arg) in args.iter().enumerate() {
+        match arg.arity {
+            Arity::Required => {
+                if let Some(previous) = optional {
+                    return Err(format!(
+                        "required argument `{}` can't follow the optional argument `{}`",
+                        arg, previous
+                    ));
+                }
+            }
+            Arity::Optional | Arity::Default(_) => optional = Some(arg),
+            Arity::Rest => {
+                if i + 1 != args.len() {
+                    return Err(format!("`{}` must be the last argument", arg));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn deserialize_args<'de, D>(deserializer: D) -> Result<Option<Vec<Argument>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let args = Option::<Vec<Argument>>::deserialize(deserializer)?;
+    if let Some(args) = &args {
+        validate_args(args).map_err(serde::de::Error::custom)?;
+    }
+
+    Ok(args)
+}
+
 impl FromStr for NamedFunction {
     type Err = String;
 
@@ -171,3 +804,77 @@ impl FromStr for NamedFunction {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn parse(s: &str) -> Argument {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_arguments() {
+        let arg = parse("string?");
+        assert_eq!(ArgumentMode::Simple, arg.mode);
+        assert_eq!(Arity::Optional, arg.arity);
+
+        let arg = parse("string=chat");
+        assert_eq!(Arity::Default("chat".to_owned()), arg.arity);
+        assert!(matches!(parse("string...").arity, Arity::Rest));
+        assert!(matches!(parse("string..").ty, Type::String(2)));
+        assert!(matches!(parse("string3").ty, Type::String(3)));
+        assert!(matches!(
+            parse("int(1..)").ty,
+            Type::Int {
+                min: Some(1),
+                max: None
+            }
+        ));
+
+        let arg = parse("url?https://lib.rs/{}");
+        assert_eq!((ArgumentMode::Test, Arity::Required), (arg.mode, arg.arity));
+        let arg = parse("url=rust!https://docs.rs/{}<!>The crate `{}` does not exist!");
+        assert_eq!(ArgumentMode::Format, arg.mode);
+        assert_eq!(Arity::Default("rust".to_owned()), arg.arity);
+
+        for s in &[
+            "string?",
+            "string=chat",
+            "string...",
+            "string2",
+            "url=rust?https://x/{}",
+            "name:url!https://lib.rs/crates/{}<!>The crate `{}` does not exist!",
+            "int(1..=10)<!>Pick a number from 1 to 10",
+            "choice(rock|paper|scissors)!",
+            "target:user!",
+            "duration=5m",
+        ] {
+            assert_eq!(*s, parse(s).to_string());
+        }
+
+        assert!("url...!https://lib.rs/{}".parse::<Argument>().is_err());
+        assert!("string=".parse::<Argument>().is_err());
+        assert!("number".parse::<Argument>().is_err());
+    }
+
+    #[test]
+    fn argument_labels() {
+        assert_eq!("<name>", parse("name:url!https://lib.rs/crates/{}").label());
+        assert_eq!("[string]", parse("string?").label());
+        assert_eq!("[who=chat]", parse("who:string=chat").label());
+        assert_eq!("[words...]", parse("words:string...").label());
+        assert_eq!("<string string>", parse("string2").label());
+    }
+
+    #[test]
+    fn validate_positions() {
+        let args = |list: &[&str]| list.iter().map(|s| parse(s)).collect::<Vec<_>>();
+
+        assert!(validate_args(&args(&["string", "string?", "string..."])).is_ok());
+        assert!(validate_args(&args(&["string=chat", "string"])).is_err());
+        assert!(validate_args(&args(&["string...", "string"])).is_err());
+    }
+}
@@ -0,0 +1,62 @@
+//! Locations of all files that the bot writes at runtime.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+
+/// The directory that holds all data written by the bot, like its state, backups and logs.
+/// Giving each bot instance its own data directory allows to run several of them side by side.
+#[derive(Clone, Debug)]
+pub struct DataDir {
+    root: PathBuf,
+}
+
+impl DataDir {
+    /// Use the given directory for all data, creating it if it doesn't exist yet. Fails right away
+    /// if the directory isn't writable, instead of only when the state is saved the first time.
+    pub fn new(root: impl Into<PathBuf>) -> Result<Self> {
+        let root = root.into();
+        fs::create_dir_all(&root)
+            .with_context(|| format!("failed creating data directory `{}`", root.display()))?;
+
+        let probe = root.join(".write-test");
+        fs::write(&probe, b"")
+            .and_then(|_| fs::remove_file(&probe))
+            .with_context(|| format!("data directory `{}` is not writable", root.display()))?;
+
+        Ok(Self { root })
+    }
+
+    /// The root of the data directory.
+    #[must_use]
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Location of a state file with the given name.
+    #[must_use]
+    pub fn state(&self, name: &str) -> PathBuf {
+        self.root.join(name)
+    }
+
+    /// Directory for backups of the state, created on first use.
+    pub fn backups(&self) -> Result<PathBuf> {
+        self.subdir("backups")
+    }
+
+    /// Directory for log files, created on first use.
+    pub fn logs(&self) -> Result<PathBuf> {
+        self.subdir("logs")
+    }
+
+    fn subdir(&self, name: &str) -> Result<PathBuf> {
+        let path = self.root.join(name);
+        fs::create_dir_all(&path)
+            .with_context(|| format!("failed creating directory `{}`", path.display()))?;
+
+        Ok(path)
+    }
+}
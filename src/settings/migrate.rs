@@ -0,0 +1,149 @@
+//! Migrations that upgrade persisted state from older versions to the current one.
+//!
+//! Migrations work on the raw JSON representation, so old formats don't have to be kept around as
+//! Rust types. Each migration only has to know about the version right before it.
+
+use anyhow::{ensure, Context, Result};
+use serde_json::{json, Map, Value};
+
+/// Version of the state format that is written by this build.
+pub const CURRENT_VERSION: u64 = 1;
+
+/// A single upgrade step, that changes the state in place.
+type Migration = fn(&mut Map<String, Value>) -> Result<()>;
+
+/// All upgrade steps, where the entry at index `n` migrates the state from version `n` to `n + 1`.
+const MIGRATIONS: &[Migration] = &[v0_to_v1];
+
+/// Upgrade the state to the [`CURRENT_VERSION`]. State without a version predates versioning and
+/// is treated as version 0.
+///
+/// Returns the version the state had before, or [`None`] if it was already up to date.
+pub fn migrate(state: &mut Value) -> Result<Option<u64>> {
+    let state = state
+        .as_object_mut()
+        .context("state must be a JSON object")?;
+    let version = match state.get("version") {
+        Some(version) => version
+            .as_u64()
+            .context("state version must be a positive number")?,
+        None => 0,
+    };
+
+    ensure!(
+        version <= CURRENT_VERSION,
+        "state version {} is newer than the supported version {}",
+        version,
+        CURRENT_VERSION
+    );
+
+    if version == CURRENT_VERSION {
+        return Ok(None);
+    }
+
+    let pending = MIGRATIONS
+        .iter()
+        .zip(0_u64..)
+        .skip_while(|(_, from)| *from < version);
+    for (migration, from) in pending {
+        migration(state)
+            .with_context(|| format!("failed migrating state from version {}", from))?;
+    }
+
+    state.insert("version".to_owned(), CURRENT_VERSION.into());
+
+    Ok(Some(version))
+}
+
+/// Version 0 stored runtime custom commands as a plain text per source and might not contain the
+/// `last_executed` field yet.
+fn v0_to_v1(state: &mut Map<String, Value>) -> Result<()> {
+    if let Some(commands) = state
+        .get_mut("custom_commands")
+        .and_then(Value::as_object_mut)
+    {
+        for (name, command) in commands {
+            let sources = command
+                .as_object()
+                .with_context(|| format!("custom command `{}` must be an object", name))?;
+
+            // Commands in the new model already exist without a version, but they never use
+            // source names as keys.
+            if sources.is_empty() || !sources.keys().all(|key| is_source(key)) {
+                continue;
+            }
+
+            let platforms = sources.keys().cloned().collect::<Vec<_>>();
+            *command = json! {{
+                "format": command.take(),
+                "platforms": platforms,
+            }};
+        }
+    }
+
+    state
+        .entry("last_executed")
+        .or_insert_with(|| Value::Object(Map::new()));
+
+    Ok(())
+}
+
+fn is_source(name: &str) -> bool {
+    matches!(name, "Discord" | "Twitch")
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::{
+        settings::{FormatString, State},
+        Source,
+    };
+
+    fn load(fixture: &str) -> (Option<u64>, State) {
+        let mut value = serde_json::from_str(fixture).unwrap();
+        let from = migrate(&mut value).unwrap();
+        (from, serde_json::from_value(value).unwrap())
+    }
+
+    #[test]
+    fn migrate_v0() {
+        let (from, state) = load(include_str!("fixtures/state_v0.json"));
+        assert_eq!(Some(0), from);
+        assert_eq!(CURRENT_VERSION, state.version);
+        assert!(state.last_executed.is_empty());
+
+        let command = &state.custom_commands["hello"];
+        assert_eq!(vec![Source::Twitch], command.platforms);
+        match &command.format {
+            Some(FormatString::Specific(map)) => assert_eq!("Hello Twitch!", map[&Source::Twitch]),
+            _ => panic!("expected a source specific format"),
+        }
+    }
+
+    #[test]
+    fn migrate_v0_with_new_commands() {
+        let (from, state) = load(include_str!("fixtures/state_v0_commands.json"));
+        assert_eq!(Some(0), from);
+
+        let command = &state.custom_commands["hi"];
+        assert_eq!(vec![Source::Discord], command.platforms);
+        assert_eq!(Some(vec!["hey".to_owned()]), command.aliases);
+        assert!(state.last_executed.contains_key("hi"));
+    }
+
+    #[test]
+    fn current_version_unchanged() {
+        let (from, state) = load(include_str!("fixtures/state_v1.json"));
+        assert_eq!(None, from);
+        assert!(state.paused_timers.contains("discord"));
+    }
+
+    #[test]
+    fn reject_newer_version() {
+        let mut value = json! {{ "version": CURRENT_VERSION + 1 }};
+        assert!(migrate(&mut value).is_err());
+    }
+}
@@ -1,21 +1,37 @@
 //! All configuration and state loading/saving logic.
 
-use std::io::ErrorKind;
 #[cfg(test)]
 use std::{collections::hash_map::DefaultHasher, hash::BuildHasherDefault};
 
-use anyhow::Result;
+use std::{hash::BuildHasher, path::Path};
+
+use anyhow::{Context, Result};
 use chrono::prelude::*;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, Serializer};
 use tokio::fs;
 
-pub use self::config::Config;
-pub use self::config_parsing::Platform;
+pub use self::config::{Config, Platform};
 use self::config_parsing::ConfigDto;
-use crate::Source;
+pub use self::config_parsing::{
+    validate_args, Announcements, Argument, ArgumentMode, Arity, Backups, Color, Command,
+    CommandItem, Counter, Crates, Discord, Embed, EmbedField, Fetch, Format, FormatString, Links,
+    Moderation, ModerationAction, Playground, Storage, Target, Timer, Twitch,
+};
+pub use self::dirs::DataDir;
+pub use self::migrate::migrate as migrate_state;
+pub use self::quotes::{Quote, Quotes};
+pub use self::schedule::{ExtraStream, ScheduleExceptions};
+pub use self::store::{
+    open as open_store, Change, JsonStore, SharedState, SqliteStore, StateStore, StateWriteGuard,
+};
 
 mod config;
 mod config_parsing;
+mod dirs;
+mod migrate;
+mod quotes;
+mod schedule;
+mod store;
 
 #[cfg(not(test))]
 type HashSet<T> = std::collections::HashSet<T>;
@@ -26,42 +42,126 @@ type HashMap<K, V> = std::collections::HashMap<K, V>;
 #[cfg(test)]
 type HashMap<K, V> = std::collections::HashMap<K, V, BuildHasherDefault<DefaultHasher>>;
 
-pub async fn load_config() -> Result<Config> {
-    todo!()
-    // let config = fs::read("/app/config.toml").await;
-    // let config = match config {
-    //     Ok(c) => c,
-    //     Err(_) => fs::read("config.toml").await?,
-    // };
-    //
-    // toml::from_slice::<ConfigDto>(&config)
-    //     .map_err(Into::into)
-    //     .map(Into::into)
+/// Load the config from the given location, or search the default locations `/app/config.toml`
+/// and `config.toml` if none was given.
+pub async fn load_config(path: Option<&Path>) -> Result<Config> {
+    let config = match path {
+        Some(path) => fs::read(path)
+            .await
+            .with_context(|| format!("failed reading config file `{}`", path.display()))?,
+        None => match fs::read("/app/config.toml").await {
+            Ok(c) => c,
+            Err(_) => fs::read("config.toml").await?,
+        },
+    };
+
+    toml::from_slice::<ConfigDto>(&config)
+        .map_err(Into::into)
+        .map(Into::into)
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct State {
+    /// Version of the state format, used to migrate state that was persisted by older versions.
+    pub version: u64,
     #[serde(default)]
     pub schedule: BaseSchedule,
-    #[serde(default)]
+    /// Weekdays without a stream, serialized in order from Monday to Sunday.
+    #[serde(default, serialize_with = "serialize_off_days")]
     pub off_days: HashSet<Weekday>,
     #[serde(default)]
-    pub custom_commands: HashMap<String, HashMap<Source, String>>,
+    pub schedule_exceptions: ScheduleExceptions,
+    /// Commands that were created at runtime by admins, in addition to the configured ones.
+    #[serde(default)]
+    pub custom_commands: HashMap<String, Command>,
+    #[serde(default)]
     pub last_executed: HashMap<String, DateTime<Utc>>,
+    /// Names of timers that were paused by an admin.
+    #[serde(default)]
+    pub paused_timers: HashSet<String>,
+    /// Current values of all counters, by the counter name.
+    #[serde(default)]
+    pub counters: HashMap<String, CounterState>,
+    #[serde(default)]
+    pub quotes: Quotes,
+    #[serde(default)]
+    pub reaction_roles: ReactionRoles,
 }
 
 impl Default for State {
     fn default() -> Self {
         Self {
+            version: migrate::CURRENT_VERSION,
             schedule: BaseSchedule::default(),
             off_days: [Weekday::Sat, Weekday::Sun].iter().copied().collect(),
+            schedule_exceptions: ScheduleExceptions::default(),
             custom_commands: HashMap::default(),
             last_executed: HashMap::default(),
+            paused_timers: HashSet::default(),
+            counters: HashMap::default(),
+            quotes: Quotes::default(),
+            reaction_roles: ReactionRoles::default(),
         }
     }
 }
 
-#[derive(Serialize, Deserialize)]
+/// The off days sorted from Monday to Sunday, as the set itself has no stable order.
+#[must_use]
+pub fn sorted_off_days<S: BuildHasher>(
+    days: &std::collections::HashSet<Weekday, S>,
+) -> Vec<Weekday> {
+    let mut days = days.iter().copied().collect::<Vec<_>>();
+    days.sort_by_key(Weekday::num_days_from_monday);
+    days
+}
+
+fn serialize_off_days<S>(days: &HashSet<Weekday>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    sorted_off_days(days).serialize(serializer)
+}
+
+/// The value of a single counter and when it was last changed.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct CounterState {
+    pub count: u64,
+    pub updated: DateTime<Utc>,
+}
+
+/// Self-assignable Discord roles, that users get by reacting to the role message with the role's
+/// emoji.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReactionRoles {
+    /// Channel that the role message is posted in.
+    pub channel: Option<u64>,
+    /// The posted role message, once it exists.
+    pub message: Option<u64>,
+    #[serde(default)]
+    pub roles: Vec<ReactionRole>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReactionRole {
+    /// A Unicode emoji like `🦀` or a custom one, written like `<:ferris:123456789012345678>`.
+    pub emoji: String,
+    pub role: u64,
+    pub description: String,
+}
+
+impl ReactionRole {
+    /// Name and ID of the emoji, if it's a custom one.
+    #[must_use]
+    pub fn custom_emoji(&self) -> Option<(&str, u64)> {
+        let inner = self.emoji.strip_prefix('<')?.strip_suffix('>')?;
+        let inner = inner.strip_prefix('a').unwrap_or(inner);
+        let (name, id) = inner.strip_prefix(':')?.split_once(':')?;
+
+        Some((name, id.parse().ok()?))
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct BaseSchedule {
     pub start: (NaiveTime, NaiveTime),
     pub finish: (NaiveTime, NaiveTime),
@@ -96,23 +196,14 @@ impl Default for BaseSchedule {
     }
 }
 
-pub async fn load_state() -> Result<State> {
-    let state = match fs::read("state.json").await {
-        Ok(buf) => buf,
-        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(State::default()),
-        Err(e) => return Err(e.into()),
-    };
-
-    serde_json::from_slice(&state).map_err(Into::into)
-}
-
-pub async fn save_state(state: &State) -> Result<()> {
-    let json = serde_json::to_vec_pretty(state)?;
-
-    fs::write("~temp-state.json", &json).await?;
-    fs::rename("~temp-state.json", "state.json").await?;
-
-    Ok(())
+/// The current date in the streamer's timezone, used to decide what days of the schedule are
+/// already in the past.
+#[must_use]
+pub fn today() -> NaiveDate {
+    Utc::now()
+        .with_timezone(&chrono_tz::CET)
+        .date()
+        .naive_local()
 }
 
 #[cfg(test)]
@@ -121,11 +212,13 @@ mod tests {
     use serde_json::json;
 
     use super::*;
+    use crate::Source;
 
     #[test]
     fn ser_default() {
         let output = serde_json::to_value(&State::default()).unwrap();
         let expect = json! {{
+            "version": 1,
             "schedule": {
                 "start": [
                     "07:00:00",
@@ -137,7 +230,21 @@ mod tests {
                 ]
             },
             "off_days": ["Sat", "Sun"],
-            "custom_commands": {}
+            "schedule_exceptions": {
+                "skips": [],
+                "extras": {},
+                "vacations": []
+            },
+            "custom_commands": {},
+            "last_executed": {},
+            "paused_timers": [],
+            "counters": {},
+            "quotes": [],
+            "reaction_roles": {
+                "channel": null,
+                "message": null,
+                "roles": []
+            }
         }};
 
         assert_eq!(expect, output);
@@ -146,6 +253,7 @@ mod tests {
     #[test]
     fn ser_custom() {
         let output = serde_json::to_value(&State {
+            version: 1,
             schedule: BaseSchedule {
                 start: (
                     NaiveTime::from_hms(5, 30, 0),
@@ -157,18 +265,46 @@ mod tests {
                 ),
             },
             off_days: [Weekday::Mon].iter().copied().collect(),
-            custom_commands: vec![(
-                "hello".to_owned(),
-                vec![(Source::Discord, "Hello World!".to_owned())]
+            schedule_exceptions: ScheduleExceptions {
+                skips: vec![NaiveDate::from_ymd(2021, 12, 24)]
                     .into_iter()
                     .collect(),
+                ..ScheduleExceptions::default()
+            },
+            custom_commands: vec![(
+                "hello".to_owned(),
+                Command {
+                    args: None,
+                    format: Some(FormatString::Specific(
+                        vec![(Source::Discord, "Hello World!".to_owned())]
+                            .into_iter()
+                            .collect(),
+                    )),
+                    cooldown: None,
+                    aliases: Some(vec!["hi".to_owned()]),
+                    platforms: vec![Source::Discord],
+                    fetch: None,
+                },
             )]
             .into_iter()
             .collect(),
             last_executed: HashMap::default(),
+            paused_timers: vec!["discord".to_owned()].into_iter().collect(),
+            counters: HashMap::default(),
+            quotes: Quotes::default(),
+            reaction_roles: ReactionRoles {
+                channel: Some(1),
+                message: None,
+                roles: vec![ReactionRole {
+                    emoji: "🦀".to_owned(),
+                    role: 2,
+                    description: "Rustacean".to_owned(),
+                }],
+            },
         })
         .unwrap();
         let expect = json! {{
+            "version": 1,
             "schedule": {
                 "start": [
                     "05:30:00",
@@ -180,13 +316,45 @@ mod tests {
                 ]
             },
             "off_days": ["Mon"],
+            "schedule_exceptions": {
+                "skips": ["2021-12-24"],
+                "extras": {},
+                "vacations": []
+            },
             "custom_commands": {
                 "hello": {
-                    "Discord": "Hello World!"
+                    "format": {
+                        "Discord": "Hello World!"
+                    },
+                    "aliases": ["hi"],
+                    "platforms": ["Discord"]
                 }
+            },
+            "last_executed": {},
+            "paused_timers": ["discord"],
+            "counters": {},
+            "quotes": [],
+            "reaction_roles": {
+                "channel": 1,
+                "message": null,
+                "roles": [{ "emoji": "🦀", "role": 2, "description": "Rustacean" }]
             }
         }};
 
         assert_eq!(expect, output);
     }
+
+    #[test]
+    fn custom_emojis() {
+        let role = |emoji: &str| ReactionRole {
+            emoji: emoji.to_owned(),
+            role: 1,
+            description: String::new(),
+        };
+
+        assert_eq!(None, role("🦀").custom_emoji());
+        assert_eq!(Some(("ferris", 123)), role("<:ferris:123>").custom_emoji());
+        assert_eq!(Some(("party", 456)), role("<a:party:456>").custom_emoji());
+        assert_eq!(None, role("<:ferris:abc>").custom_emoji());
+    }
 }
@@ -0,0 +1,187 @@
+//! Memorable quotes that were collected from chat.
+
+use std::iter::FromIterator;
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::Source;
+
+/// A single quote, together with some details about when and where it was added.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Quote {
+    /// Number to refer to the quote, which never changes once assigned.
+    pub number: u32,
+    pub text: String,
+    /// Name of the user that added the quote.
+    pub author: String,
+    /// Date at which the quote was added.
+    pub date: NaiveDate,
+    /// Platform that the quote was added on.
+    pub source: Source,
+}
+
+/// All quotes, sorted by their number. Numbers of deleted quotes are not reused, except for the
+/// one of the most recent quote.
+#[derive(Clone, Default, Serialize)]
+#[serde(transparent)]
+pub struct Quotes(Vec<Quote>);
+
+/// Sorts the quotes while loading, as the lookups by number rely on it.
+impl<'de> Deserialize<'de> for Quotes {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Vec::<Quote>::deserialize(deserializer).map(|quotes| quotes.into_iter().collect())
+    }
+}
+
+impl Quotes {
+    /// All quotes, sorted by their number.
+    #[must_use]
+    pub fn all(&self) -> &[Quote] {
+        &self.0
+    }
+
+    /// Add a new quote with the next free number and return that number.
+    pub fn add(&mut self, text: String, author: String, date: NaiveDate, source: Source) -> u32 {
+        let number = self.0.last().map_or(1, |quote| quote.number + 1);
+        self.0.push(Quote {
+            number,
+            text,
+            author,
+            date,
+            source,
+        });
+
+        number
+    }
+
+    /// Insert the given quote, replacing any existing one with the same number.
+    pub fn insert(&mut self, quote: Quote) {
+        match self.position(quote.number) {
+            Ok(index) => self.0[index] = quote,
+            Err(index) => self.0.insert(index, quote),
+        }
+    }
+
+    #[must_use]
+    pub fn get(&self, number: u32) -> Option<&Quote> {
+        self.position(number).ok().map(|index| &self.0[index])
+    }
+
+    pub fn get_mut(&mut self, number: u32) -> Option<&mut Quote> {
+        self.position(number)
+            .ok()
+            .map(move |index| &mut self.0[index])
+    }
+
+    /// Remove the quote with the given number. Returns whether it existed.
+    pub fn remove(&mut self, number: u32) -> bool {
+        self.position(number)
+            .map(|index| self.0.remove(index))
+            .is_ok()
+    }
+
+    /// Find all quotes that contain each of the given words, ignoring case.
+    #[must_use]
+    pub fn search(&self, keywords: &str) -> Vec<&Quote> {
+        let keywords = keywords
+            .split_whitespace()
+            .map(str::to_lowercase)
+            .collect::<Vec<_>>();
+
+        self.0
+            .iter()
+            .filter(|quote| {
+                let text = quote.text.to_lowercase();
+                keywords
+                    .iter()
+                    .all(|keyword| text.contains(keyword.as_str()))
+            })
+            .collect()
+    }
+
+    fn position(&self, number: u32) -> Result<usize, usize> {
+        self.0.binary_search_by_key(&number, |quote| quote.number)
+    }
+}
+
+impl FromIterator<Quote> for Quotes {
+    fn from_iter<T: IntoIterator<Item = Quote>>(iter: T) -> Self {
+        let mut quotes = iter.into_iter().collect::<Vec<_>>();
+        quotes.sort_by_key(|quote| quote.number);
+        Self(quotes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn numbers(quotes: &[&Quote]) -> Vec<u32> {
+        quotes.iter().map(|quote| quote.number).collect()
+    }
+
+    #[test]
+    fn add_remove_search() {
+        let date = NaiveDate::from_ymd(2021, 6, 1);
+        let mut quotes = Quotes::default();
+
+        for text in &["Rust is great", "I love Rust", "Who needs tests?"] {
+            quotes.add(
+                (*text).to_owned(),
+                "dnaka91".to_owned(),
+                date,
+                Source::Twitch,
+            );
+        }
+
+        assert_eq!(vec![1, 2], numbers(&quotes.search("rust")));
+        assert_eq!(vec![2], numbers(&quotes.search("LOVE rust")));
+
+        assert!(quotes.remove(3));
+        assert!(!quotes.remove(3));
+        // Deleting the most recent quote frees its number again, but others are never reused.
+        assert_eq!(
+            3,
+            quotes.add(
+                "Oops".to_owned(),
+                "dnaka91".to_owned(),
+                date,
+                Source::Discord
+            )
+        );
+        assert!(quotes.remove(1));
+        assert_eq!(
+            4,
+            quotes.add(
+                "Again".to_owned(),
+                "dnaka91".to_owned(),
+                date,
+                Source::Discord
+            )
+        );
+        assert!(quotes.get(1).is_none());
+        assert_eq!("Again", quotes.get(4).unwrap().text);
+    }
+
+    #[test]
+    fn sort_on_load() {
+        let quotes = serde_json::from_value::<Quotes>(serde_json::json!([
+            {"number": 7, "text": "b", "author": "a", "date": "2021-06-01", "source": "Twitch"},
+            {"number": 2, "text": "a", "author": "a", "date": "2021-06-01", "source": "Discord"},
+        ]))
+        .unwrap();
+
+        assert_eq!("a", quotes.get(2).unwrap().text);
+        assert_eq!("b", quotes.get(7).unwrap().text);
+        assert_eq!(
+            vec![2, 7],
+            numbers(&quotes.all().iter().collect::<Vec<_>>())
+        );
+    }
+}
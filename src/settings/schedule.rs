@@ -0,0 +1,164 @@
+//! Date based exceptions to the regular weekly schedule.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// One-off changes to the regular schedule, like skipped days, additional streams or whole
+/// vacations. All dates are in the streamer's local timezone.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct ScheduleExceptions {
+    /// Single days that would normally have a stream but are skipped.
+    #[serde(default)]
+    pub skips: BTreeSet<NaiveDate>,
+    /// Additional streams on specific days, outside of the regular schedule.
+    #[serde(default)]
+    pub extras: BTreeMap<NaiveDate, ExtraStream>,
+    /// Inclusive date ranges without any streams.
+    #[serde(default)]
+    pub vacations: BTreeSet<(NaiveDate, NaiveDate)>,
+}
+
+/// A stream that happens in addition to the regular schedule.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ExtraStream {
+    pub start: NaiveTime,
+    pub finish: NaiveTime,
+    pub title: Option<String>,
+}
+
+impl ScheduleExceptions {
+    /// Remove all exceptions that lie completely before the given date. Returns whether anything
+    /// was removed.
+    pub fn prune(&mut self, today: NaiveDate) -> bool {
+        let before = self.len();
+
+        self.skips = self.skips.split_off(&today);
+        self.extras = self.extras.split_off(&today);
+        self.vacations.retain(|(_, end)| *end >= today);
+
+        before != self.len()
+    }
+
+    /// Whether the given date was explicitly taken off, either by a skip or a vacation.
+    #[must_use]
+    pub fn is_off(&self, date: NaiveDate) -> bool {
+        self.skips.contains(&date)
+            || self
+                .vacations
+                .iter()
+                .any(|(start, end)| (*start..=*end).contains(&date))
+    }
+
+    /// Remove any exception that covers the given date. Returns whether anything was removed.
+    pub fn remove(&mut self, date: NaiveDate) -> bool {
+        let before = self.len();
+
+        self.skips.remove(&date);
+        self.extras.remove(&date);
+        self.vacations
+            .retain(|(start, end)| !(*start..=*end).contains(&date));
+
+        before != self.len()
+    }
+
+    /// Human readable descriptions of all exceptions that are not yet in the past, sorted by their
+    /// date.
+    #[must_use]
+    pub fn describe(&self, today: NaiveDate) -> Vec<String> {
+        let skips = self
+            .skips
+            .range(today..)
+            .map(|date| (*date, format!("{}: no stream", format_date(*date))));
+
+        let extras = self.extras.range(today..).map(|(date, extra)| {
+            let mut line = format!(
+                "{}: extra stream from {} to {}",
+                format_date(*date),
+                extra.start.format("%I:%M%P"),
+                extra.finish.format("%I:%M%P"),
+            );
+            if let Some(title) = &extra.title {
+                line.push_str(" (");
+                line.push_str(title);
+                line.push(')');
+            }
+            (*date, line)
+        });
+
+        let vacations =
+            self.vacations
+                .iter()
+                .filter(|(_, end)| *end >= today)
+                .map(|(start, end)| {
+                    let line =
+                        format!("{} to {}: vacation", format_date(*start), format_date(*end));
+                    (*start, line)
+                });
+
+        let mut list = skips.chain(extras).chain(vacations).collect::<Vec<_>>();
+        list.sort_by_key(|(date, _)| *date);
+        list.into_iter().map(|(_, line)| line).collect()
+    }
+
+    fn len(&self) -> usize {
+        self.skips.len() + self.extras.len() + self.vacations.len()
+    }
+}
+
+fn format_date(date: NaiveDate) -> String {
+    date.format("%a, %b %d %Y").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn date(m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd(2026, m, d)
+    }
+
+    #[test]
+    fn prune_past() {
+        let mut exceptions = ScheduleExceptions::default();
+        exceptions.skips.insert(date(12, 1));
+        exceptions.skips.insert(date(12, 24));
+        exceptions.extras.insert(
+            date(11, 30),
+            ExtraStream {
+                start: NaiveTime::from_hms(14, 0, 0),
+                finish: NaiveTime::from_hms(18, 0, 0),
+                title: None,
+            },
+        );
+        exceptions.vacations.insert((date(11, 20), date(12, 2)));
+        exceptions.vacations.insert((date(11, 1), date(11, 14)));
+
+        assert!(exceptions.prune(date(12, 2)));
+        assert_eq!(
+            vec![date(12, 24)],
+            exceptions.skips.iter().copied().collect::<Vec<_>>()
+        );
+        assert!(exceptions.extras.is_empty());
+        assert_eq!(1, exceptions.vacations.len());
+        assert!(!exceptions.prune(date(12, 2)));
+    }
+
+    #[test]
+    fn off_days() {
+        let mut exceptions = ScheduleExceptions::default();
+        exceptions.skips.insert(date(12, 24));
+        exceptions.vacations.insert((date(8, 1), date(8, 14)));
+
+        assert!(exceptions.is_off(date(12, 24)));
+        assert!(exceptions.is_off(date(8, 1)));
+        assert!(exceptions.is_off(date(8, 14)));
+        assert!(!exceptions.is_off(date(8, 15)));
+
+        assert!(exceptions.remove(date(8, 5)));
+        assert!(!exceptions.is_off(date(8, 1)));
+    }
+}
@@ -0,0 +1,734 @@
+//! Storage backends that persist the bot's [`State`].
+
+use std::{
+    borrow::Cow,
+    io::ErrorKind,
+    ops::{Deref, DerefMut},
+    path::PathBuf,
+    sync::Mutex,
+};
+
+use anyhow::{anyhow, ensure, Result};
+use chrono::prelude::*;
+use log::{error, info};
+use rusqlite::{params, Connection, Transaction};
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::sync::{
+    mpsc::{self, UnboundedReceiver, UnboundedSender},
+    oneshot, RwLock, RwLockReadGuard, RwLockWriteGuard,
+};
+
+use super::{
+    migrate, sorted_off_days, BaseSchedule, Command, CounterState, DataDir, HashSet, Quote,
+    ReactionRoles, ScheduleExceptions, State, Storage,
+};
+
+/// A single part of the state that was changed and needs to be persisted.
+#[derive(Clone, Debug)]
+pub enum Change<'a> {
+    /// The base schedule, off days or schedule exceptions.
+    Schedule,
+    /// A runtime custom command, which might have been removed as well.
+    CustomCommand(Cow<'a, str>),
+    /// The last execution time of a command with a cooldown.
+    Cooldown(Cow<'a, str>),
+    /// The paused state of timers.
+    Timers,
+    /// The value of a single counter.
+    Counter(Cow<'a, str>),
+    /// A single quote, which might have been removed as well.
+    Quote(u32),
+    /// The self-assignable Discord roles and their message.
+    ReactionRoles,
+    /// Any part of the state, after bulk changes like an import.
+    All,
+}
+
+/// The new value of a changed part of the state, that is sent to the writer instead of a copy of
+/// the whole state.
+enum Entity {
+    Schedule(BaseSchedule, HashSet<Weekday>, ScheduleExceptions),
+    CustomCommand(String, Option<Box<Command>>),
+    Cooldown(String, Option<DateTime<Utc>>),
+    Timers(HashSet<String>),
+    Counter(String, Option<CounterState>),
+    Quote(u32, Option<Quote>),
+    ReactionRoles(ReactionRoles),
+    All(Box<State>),
+}
+
+impl Entity {
+    /// Take the current value of the changed part from the state.
+    fn capture(state: &State, change: Change<'_>) -> Self {
+        match change {
+            Change::Schedule => Self::Schedule(
+                state.schedule.clone(),
+                state.off_days.clone(),
+                state.schedule_exceptions.clone(),
+            ),
+            Change::CustomCommand(name) => {
+                let command = state
+                    .custom_commands
+                    .get(name.as_ref())
+                    .cloned()
+                    .map(Box::new);
+                Self::CustomCommand(name.into_owned(), command)
+            }
+            Change::Cooldown(name) => {
+                let last_executed = state.last_executed.get(name.as_ref()).copied();
+                Self::Cooldown(name.into_owned(), last_executed)
+            }
+            Change::Timers => Self::Timers(state.paused_timers.clone()),
+            Change::Counter(name) => {
+                let counter = state.counters.get(name.as_ref()).copied();
+                Self::Counter(name.into_owned(), counter)
+            }
+            Change::Quote(number) => Self::Quote(number, state.quotes.get(number).cloned()),
+            Change::ReactionRoles => Self::ReactionRoles(state.reaction_roles.clone()),
+            Change::All => Self::All(Box::new(state.clone())),
+        }
+    }
+
+    /// Put the new value into the given copy of the state and tell which part changed.
+    fn apply(self, state: &mut State) -> Change<'static> {
+        fn put<T>(map: &mut super::HashMap<String, T>, name: &str, value: Option<T>) {
+            match value {
+                Some(value) => map.insert(name.to_owned(), value),
+                None => map.remove(name),
+            };
+        }
+
+        match self {
+            Self::Schedule(schedule, off_days, exceptions) => {
+                state.schedule = schedule;
+                state.off_days = off_days;
+                state.schedule_exceptions = exceptions;
+                Change::Schedule
+            }
+            Self::CustomCommand(name, command) => {
+                put(
+                    &mut state.custom_commands,
+                    &name,
+                    command.map(|command| *command),
+                );
+                Change::CustomCommand(Cow::Owned(name))
+            }
+            Self::Cooldown(name, last_executed) => {
+                put(&mut state.last_executed, &name, last_executed);
+                Change::Cooldown(Cow::Owned(name))
+            }
+            Self::Timers(paused_timers) => {
+                state.paused_timers = paused_timers;
+                Change::Timers
+            }
+            Self::Counter(name, counter) => {
+                put(&mut state.counters, &name, counter);
+                Change::Counter(Cow::Owned(name))
+            }
+            Self::Quote(number, quote) => {
+                match quote {
+                    Some(quote) => state.quotes.insert(quote),
+                    None => {
+                        state.quotes.remove(number);
+                    }
+                }
+                Change::Quote(number)
+            }
+            Self::ReactionRoles(reaction_roles) => {
+                state.reaction_roles = reaction_roles;
+                Change::ReactionRoles
+            }
+            Self::All(new) => {
+                *state = *new;
+                Change::All
+            }
+        }
+    }
+}
+
+/// Backend that can load and persist the whole [`State`].
+pub trait StateStore: Send + Sync {
+    /// Load the state, or create the default one if nothing was persisted yet.
+    fn load(&self) -> Result<State>;
+
+    /// Persist the whole state.
+    fn save(&self, state: &State) -> Result<()>;
+
+    /// Persist a single changed entity of the state. By default this saves the whole state, but
+    /// backends can implement more fine grained updates.
+    fn update(&self, state: &State, _change: Change<'_>) -> Result<()> {
+        self.save(state)
+    }
+}
+
+/// Open the configured storage backend, with its files located in the given data directory.
+pub fn open(storage: Storage, dirs: &DataDir) -> Result<Box<dyn StateStore>> {
+    Ok(match storage {
+        Storage::Json => Box::new(JsonStore::new(dirs.state("state.json"))),
+        Storage::Sqlite => {
+            let store = SqliteStore::open(dirs.state("state.db"))?;
+            let json = dirs.state("state.json");
+
+            // Switching from the JSON file keeps the existing state, until the database is used.
+            if store.is_empty()? && json.exists() {
+                info!("importing state from {}", json.display());
+                store.save(&JsonStore::new(json).load()?)?;
+            }
+
+            Box::new(store)
+        }
+    })
+}
+
+/// The [`State`] shared between all parts of the bot, together with the writer that persists it.
+pub struct SharedState {
+    state: RwLock<State>,
+    writer: UnboundedSender<Write>,
+}
+
+/// A change to the state, that is waiting to be persisted.
+struct Write {
+    entity: Entity,
+    done: Option<oneshot::Sender<Result<()>>>,
+}
+
+impl SharedState {
+    /// Load the state from the given store. Changes are persisted by a background thread in the
+    /// order they were made, so the lock on the state is never held while writing to disk.
+    pub fn load(store: Box<dyn StateStore>) -> Result<Self> {
+        let mut state = store.load()?;
+        state.schedule_exceptions.prune(super::today());
+
+        let (writer, pending) = mpsc::unbounded_channel();
+        let copy = state.clone();
+        std::thread::spawn(move || write(&*store, copy, pending));
+
+        Ok(Self {
+            state: RwLock::new(state),
+            writer,
+        })
+    }
+
+    /// Lock the state for reading.
+    pub async fn read(&self) -> RwLockReadGuard<'_, State> {
+        self.state.read().await
+    }
+
+    /// Lock the state for writing. Changes must be persisted with [`StateWriteGuard::persist`].
+    pub async fn write(&self) -> StateWriteGuard<'_> {
+        StateWriteGuard {
+            guard: self.state.write().await,
+            writer: &self.writer,
+        }
+    }
+
+    /// Persist the whole state at once and wait until it's written.
+    pub async fn save(&self) -> Result<()> {
+        let (done, result) = oneshot::channel();
+        let entity = Entity::capture(&*self.state.read().await, Change::All);
+        self.writer
+            .send(Write {
+                entity,
+                done: Some(done),
+            })
+            .map_err(|_e| anyhow!("state writer stopped"))?;
+
+        result.await.map_err(|_e| anyhow!("state writer stopped"))?
+    }
+}
+
+/// Write the queued changes one after another, without blocking the runtime with file or
+/// database access. The writer keeps its own copy of the state, for stores that can only persist
+/// all of it at once.
+fn write(store: &dyn StateStore, mut state: State, mut pending: UnboundedReceiver<Write>) {
+    while let Some(Write { entity, done }) = pending.blocking_recv() {
+        let change = entity.apply(&mut state);
+        let res = store.update(&state, change);
+
+        match done {
+            Some(done) => {
+                done.send(res).ok();
+            }
+            None => {
+                if let Err(e) = res {
+                    error!("failed persisting state: {}", e);
+                }
+            }
+        }
+    }
+}
+
+/// Write access to the shared [`State`], that allows to persist changes to it.
+pub struct StateWriteGuard<'a> {
+    guard: RwLockWriteGuard<'a, State>,
+    writer: &'a UnboundedSender<Write>,
+}
+
+impl<'a> StateWriteGuard<'a> {
+    /// Persist the given change, that was made to the state through this guard. The change is
+    /// written in the background, after the lock was released.
+    pub fn persist(&self, change: Change<'_>) -> Result<()> {
+        self.writer
+            .send(Write {
+                entity: Entity::capture(&self.guard, change),
+                done: None,
+            })
+            .map_err(|_e| anyhow!("state writer stopped"))
+    }
+}
+
+impl<'a> Deref for StateWriteGuard<'a> {
+    type Target = State;
+
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl<'a> DerefMut for StateWriteGuard<'a> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.guard
+    }
+}
+
+/// Store that keeps the whole state in a single JSON file, which is rewritten on every change.
+pub struct JsonStore {
+    path: PathBuf,
+    temp_path: PathBuf,
+}
+
+impl JsonStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let mut temp_name = std::ffi::OsString::from("~temp-");
+        temp_name.push(path.file_name().unwrap_or_default());
+
+        Self {
+            temp_path: path.with_file_name(temp_name),
+            path,
+        }
+    }
+}
+
+impl StateStore for JsonStore {
+    fn load(&self) -> Result<State> {
+        let buf = match std::fs::read(&self.path) {
+            Ok(buf) => buf,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(State::default()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut value = serde_json::from_slice(&buf)?;
+        let migrated = migrate::migrate(&mut value)?;
+        let state = serde_json::from_value(value)?;
+
+        if let Some(version) = migrated {
+            // Keep the original file around, in case the migration lost anything.
+            let mut backup = self.path.clone().into_os_string();
+            backup.push(format!(".v{}.bak", version));
+            std::fs::write(&backup, &buf)?;
+
+            info!(
+                "migrated state from version {} to {}",
+                version,
+                migrate::CURRENT_VERSION
+            );
+            self.save(&state)?;
+        }
+
+        Ok(state)
+    }
+
+    fn save(&self, state: &State) -> Result<()> {
+        let json = serde_json::to_vec_pretty(state)?;
+
+        std::fs::write(&self.temp_path, &json)?;
+        std::fs::rename(&self.temp_path, &self.path)?;
+
+        Ok(())
+    }
+
+    fn update(&self, state: &State, change: Change<'_>) -> Result<()> {
+        match change {
+            // Rewriting the whole file for every command use is too expensive. Cooldowns are
+            // still saved together with any other change or when shutting down.
+            Change::Cooldown(_) => Ok(()),
+            _ => self.save(state),
+        }
+    }
+}
+
+/// Store that keeps the state in an embedded SQLite database, with a table for each kind of
+/// entity. Updates only touch the changed entity, each in its own transaction.
+pub struct SqliteStore {
+    conn: Mutex<Connection>,
+}
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS settings (
+        key   TEXT PRIMARY KEY NOT NULL,
+        value TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS custom_commands (
+        name    TEXT PRIMARY KEY NOT NULL,
+        command TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS cooldowns (
+        name          TEXT PRIMARY KEY NOT NULL,
+        last_executed TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS counters (
+        name    TEXT PRIMARY KEY NOT NULL,
+        counter TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS quotes (
+        number INTEGER PRIMARY KEY NOT NULL,
+        quote  TEXT NOT NULL
+    );
+";
+
+impl SqliteStore {
+    /// Open or create the database at the given location.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        Self::init(Connection::open(path.into())?)
+    }
+
+    /// Create a new database that only lives in memory, mostly useful for tests.
+    pub fn in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        let version =
+            conn.query_row("PRAGMA user_version", params![], |row| row.get::<_, u32>(0))?;
+        ensure!(
+            u64::from(version) <= migrate::CURRENT_VERSION,
+            "state database version {} is newer than the supported version {}",
+            version,
+            migrate::CURRENT_VERSION
+        );
+
+        // The database always stores entities in the current format, so only the schema needs to
+        // be created, without migrating any existing data.
+        conn.execute_batch(SCHEMA)?;
+        conn.execute_batch(&format!(
+            "PRAGMA user_version = {}",
+            migrate::CURRENT_VERSION
+        ))?;
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    /// Whether nothing was stored in the database yet.
+    pub fn is_empty(&self) -> Result<bool> {
+        self.transaction(|tx| {
+            let empty = tx.query_row(
+                "SELECT NOT EXISTS (SELECT 1 FROM settings)
+                    AND NOT EXISTS (SELECT 1 FROM custom_commands)
+                    AND NOT EXISTS (SELECT 1 FROM cooldowns)
+                    AND NOT EXISTS (SELECT 1 FROM counters)
+                    AND NOT EXISTS (SELECT 1 FROM quotes)",
+                params![],
+                |row| row.get(0),
+            )?;
+            Ok(empty)
+        })
+    }
+
+    fn transaction<T>(&self, f: impl FnOnce(&Transaction<'_>) -> Result<T>) -> Result<T> {
+        let mut conn = self
+            .conn
+            .lock()
+            .map_err(|_e| anyhow!("state database lock poisoned"))?;
+        let tx = conn.transaction()?;
+        let value = f(&tx)?;
+        tx.commit()?;
+
+        Ok(value)
+    }
+}
+
+fn put_setting(tx: &Transaction<'_>, key: &str, value: &impl Serialize) -> Result<()> {
+    tx.execute(
+        "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
+        params![key, serde_json::to_string(value)?],
+    )?;
+    Ok(())
+}
+
+fn get_setting<T: DeserializeOwned>(tx: &Transaction<'_>, key: &str) -> Result<Option<T>> {
+    let mut stmt = tx.prepare("SELECT value FROM settings WHERE key = ?1")?;
+    let mut rows = stmt.query(params![key])?;
+
+    let value = match rows.next()? {
+        Some(row) => Some(serde_json::from_str(&row.get::<_, String>(0)?)?),
+        None => None,
+    };
+
+    Ok(value)
+}
+
+fn put_schedule(tx: &Transaction<'_>, state: &State) -> Result<()> {
+    put_setting(tx, "schedule", &state.schedule)?;
+    put_setting(tx, "off_days", &sorted_off_days(&state.off_days))?;
+    put_setting(tx, "schedule_exceptions", &state.schedule_exceptions)
+}
+
+fn put_custom_command(tx: &Transaction<'_>, state: &State, name: &str) -> Result<()> {
+    if let Some(command) = state.custom_commands.get(name) {
+        tx.execute(
+            "INSERT OR REPLACE INTO custom_commands (name, command) VALUES (?1, ?2)",
+            params![name, serde_json::to_string(command)?],
+        )?;
+    } else {
+        tx.execute("DELETE FROM custom_commands WHERE name = ?1", params![name])?;
+    }
+    Ok(())
+}
+
+fn put_cooldown(tx: &Transaction<'_>, state: &State, name: &str) -> Result<()> {
+    if let Some(last_executed) = state.last_executed.get(name) {
+        tx.execute(
+            "INSERT OR REPLACE INTO cooldowns (name, last_executed) VALUES (?1, ?2)",
+            params![name, last_executed.to_rfc3339()],
+        )?;
+    }
+    Ok(())
+}
+
+fn put_counter(tx: &Transaction<'_>, state: &State, name: &str) -> Result<()> {
+    if let Some(counter) = state.counters.get(name) {
+        tx.execute(
+            "INSERT OR REPLACE INTO counters (name, counter) VALUES (?1, ?2)",
+            params![name, serde_json::to_string(counter)?],
+        )?;
+    }
+    Ok(())
+}
+
+fn put_quote(tx: &Transaction<'_>, state: &State, number: u32) -> Result<()> {
+    if let Some(quote) = state.quotes.get(number) {
+        tx.execute(
+            "INSERT OR REPLACE INTO quotes (number, quote) VALUES (?1, ?2)",
+            params![number, serde_json::to_string(quote)?],
+        )?;
+    } else {
+        tx.execute("DELETE FROM quotes WHERE number = ?1", params![number])?;
+    }
+    Ok(())
+}
+
+fn put_all(tx: &Transaction<'_>, state: &State) -> Result<()> {
+    put_schedule(tx, state)?;
+    put_setting(tx, "paused_timers", &state.paused_timers)?;
+    put_setting(tx, "reaction_roles", &state.reaction_roles)?;
+
+    tx.execute("DELETE FROM custom_commands", params![])?;
+    for name in state.custom_commands.keys() {
+        put_custom_command(tx, state, name)?;
+    }
+
+    tx.execute("DELETE FROM cooldowns", params![])?;
+    for name in state.last_executed.keys() {
+        put_cooldown(tx, state, name)?;
+    }
+
+    tx.execute("DELETE FROM counters", params![])?;
+    for name in state.counters.keys() {
+        put_counter(tx, state, name)?;
+    }
+
+    tx.execute("DELETE FROM quotes", params![])?;
+    for quote in state.quotes.all() {
+        put_quote(tx, state, quote.number)?;
+    }
+
+    Ok(())
+}
+
+impl StateStore for SqliteStore {
+    fn load(&self) -> Result<State> {
+        self.transaction(|tx| {
+            let mut state = State::default();
+
+            if let Some(schedule) = get_setting(tx, "schedule")? {
+                state.schedule = schedule;
+            }
+            if let Some(off_days) = get_setting(tx, "off_days")? {
+                state.off_days = off_days;
+            }
+            if let Some(exceptions) = get_setting(tx, "schedule_exceptions")? {
+                state.schedule_exceptions = exceptions;
+            }
+            if let Some(paused_timers) = get_setting(tx, "paused_timers")? {
+                state.paused_timers = paused_timers;
+            }
+            if let Some(reaction_roles) = get_setting(tx, "reaction_roles")? {
+                state.reaction_roles = reaction_roles;
+            }
+
+            let mut stmt = tx.prepare("SELECT name, command FROM custom_commands")?;
+            let mut rows = stmt.query(params![])?;
+            while let Some(row) = rows.next()? {
+                let command = serde_json::from_str(&row.get::<_, String>(1)?)?;
+                state.custom_commands.insert(row.get(0)?, command);
+            }
+
+            let mut stmt = tx.prepare("SELECT name, last_executed FROM cooldowns")?;
+            let mut rows = stmt.query(params![])?;
+            while let Some(row) = rows.next()? {
+                let last_executed = DateTime::parse_from_rfc3339(&row.get::<_, String>(1)?)?;
+                state
+                    .last_executed
+                    .insert(row.get(0)?, last_executed.with_timezone(&Utc));
+            }
+
+            let mut stmt = tx.prepare("SELECT name, counter FROM counters")?;
+            let mut rows = stmt.query(params![])?;
+            while let Some(row) = rows.next()? {
+                let counter = serde_json::from_str(&row.get::<_, String>(1)?)?;
+                state.counters.insert(row.get(0)?, counter);
+            }
+
+            let mut stmt = tx.prepare("SELECT quote FROM quotes")?;
+            let quotes = stmt
+                .query_map(params![], |row| row.get::<_, String>(0))?
+                .map(|quote| Ok(serde_json::from_str::<Quote>(&quote?)?))
+                .collect::<Result<_>>()?;
+            state.quotes = quotes;
+
+            Ok(state)
+        })
+    }
+
+    fn save(&self, state: &State) -> Result<()> {
+        self.transaction(|tx| put_all(tx, state))
+    }
+
+    fn update(&self, state: &State, change: Change<'_>) -> Result<()> {
+        self.transaction(|tx| match change {
+            Change::Schedule => put_schedule(tx, state),
+            Change::CustomCommand(name) => put_custom_command(tx, state, &name),
+            Change::Cooldown(name) => put_cooldown(tx, state, &name),
+            Change::Timers => put_setting(tx, "paused_timers", &state.paused_timers),
+            Change::Counter(name) => put_counter(tx, state, &name),
+            Change::Quote(number) => put_quote(tx, state, number),
+            Change::ReactionRoles => put_setting(tx, "reaction_roles", &state.reaction_roles),
+            Change::All => put_all(tx, state),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::settings::{Command, FormatString};
+
+    fn command(content: &str) -> Command {
+        Command {
+            format: Some(FormatString::Universal(content.to_owned())),
+            ..Command::empty()
+        }
+    }
+
+    #[test]
+    fn sqlite_roundtrip() {
+        let store = SqliteStore::in_memory().unwrap();
+        assert!(store.load().unwrap().custom_commands.is_empty());
+
+        let mut state = State::default();
+        state.off_days.insert(Weekday::Wed);
+        state
+            .custom_commands
+            .insert("hello".to_owned(), command("Hello!"));
+        state
+            .last_executed
+            .insert("hello".to_owned(), Utc.ymd(2021, 6, 1).and_hms(12, 0, 0));
+        state.paused_timers.insert("discord".to_owned());
+        store.save(&state).unwrap();
+
+        let loaded = store.load().unwrap();
+        assert_eq!(state.off_days, loaded.off_days);
+        assert_eq!(state.last_executed, loaded.last_executed);
+        assert_eq!(state.paused_timers, loaded.paused_timers);
+        assert!(loaded.custom_commands.contains_key("hello"));
+    }
+
+    #[test]
+    fn sqlite_update_single_entity() {
+        let store = SqliteStore::in_memory().unwrap();
+
+        let mut state = State::default();
+        state
+            .custom_commands
+            .insert("hello".to_owned(), command("Hello!"));
+        store.save(&state).unwrap();
+
+        // A second, outdated copy of the state only updates its own command and doesn't clobber
+        // the other one.
+        let mut other = State::default();
+        other
+            .custom_commands
+            .insert("bye".to_owned(), command("Bye!"));
+        store
+            .update(&other, Change::CustomCommand("bye".into()))
+            .unwrap();
+
+        let loaded = store.load().unwrap();
+        assert!(loaded.custom_commands.contains_key("hello"));
+        assert!(loaded.custom_commands.contains_key("bye"));
+
+        state.custom_commands.remove("hello");
+        store
+            .update(&state, Change::CustomCommand("hello".into()))
+            .unwrap();
+
+        let loaded = store.load().unwrap();
+        assert!(!loaded.custom_commands.contains_key("hello"));
+    }
+
+    #[test]
+    fn sqlite_imports_json() {
+        let root = std::env::temp_dir().join(format!("togglebot-import-{}", std::process::id()));
+        let dirs = DataDir::new(&root).unwrap();
+
+        let mut state = State::default();
+        state.paused_timers.insert("discord".to_owned());
+        JsonStore::new(dirs.state("state.json"))
+            .save(&state)
+            .unwrap();
+
+        let loaded = open(Storage::Sqlite, &dirs).unwrap().load().unwrap();
+        // Once the database has content, the JSON file is ignored.
+        JsonStore::new(dirs.state("state.json"))
+            .save(&State::default())
+            .unwrap();
+        let reopened = open(Storage::Sqlite, &dirs).unwrap().load().unwrap();
+        std::fs::remove_dir_all(&root).ok();
+
+        assert!(loaded.paused_timers.contains("discord"));
+        assert!(reopened.paused_timers.contains("discord"));
+    }
+
+    #[tokio::test]
+    async fn background_writes() {
+        let path = std::env::temp_dir().join(format!("togglebot-{}.json", std::process::id()));
+        let state = SharedState::load(Box::new(JsonStore::new(&path))).unwrap();
+
+        {
+            let mut guard = state.write().await;
+            guard.paused_timers.insert("discord".to_owned());
+            guard.persist(Change::Timers).unwrap();
+        }
+        // Saving waits for all earlier writes as well.
+        state.save().await.unwrap();
+
+        let loaded = JsonStore::new(&path).load().unwrap();
+        std::fs::remove_file(&path).ok();
+        assert!(loaded.paused_timers.contains("discord"));
+    }
+}
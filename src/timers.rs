@@ -0,0 +1,303 @@
+//! Timed messages that are posted repeatedly to the configured platforms, like reminders to join
+//! the Discord server.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration as StdDuration,
+};
+
+use chrono::{prelude::*, Duration};
+use log::{info, warn};
+use tokio::{select, time};
+
+use crate::{
+    announce::Clock,
+    commands,
+    handler::AsyncState,
+    settings::{Config, FormatString, State, Target},
+    Outboxes, Outgoing, Shutdown, Source,
+};
+
+/// How often the timers are checked for due messages.
+const POLL_INTERVAL: StdDuration = StdDuration::from_secs(30);
+
+/// Counter for the received chat messages of each platform instance, used to only post timers
+/// into chats that are active.
+pub struct Activity {
+    lines: HashMap<String, AtomicU64>,
+}
+
+impl Activity {
+    /// Set up a counter for every configured platform.
+    #[must_use]
+    pub fn new(config: &Config) -> Self {
+        Self {
+            lines: config
+                .platforms
+                .keys()
+                .map(|name| (name.clone(), AtomicU64::new(0)))
+                .collect(),
+        }
+    }
+
+    /// Count a single chat message received by the given platform.
+    pub fn record(&self, platform: &str) {
+        if let Some(lines) = self.lines.get(platform) {
+            lines.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn lines(&self, platform: &str) -> u64 {
+        self.lines
+            .get(platform)
+            .map_or(0, |lines| lines.load(Ordering::Relaxed))
+    }
+}
+
+/// State of all configured timers, tracking when and where each of them was last posted.
+pub struct Timers {
+    entries: Vec<Entry>,
+}
+
+struct Entry {
+    name: String,
+    message: FormatString,
+    interval: Duration,
+    min_lines: u64,
+    targets: Vec<TargetState>,
+}
+
+struct TargetState {
+    target: Target,
+    source: Source,
+    last_post: DateTime<Utc>,
+    last_lines: u64,
+}
+
+impl TargetState {
+    fn new(
+        config: &Config,
+        name: &str,
+        target: &Target,
+        activity: &Activity,
+        now: DateTime<Utc>,
+    ) -> Option<Self> {
+        let source = if let Some(platform) = config.platforms.get(&target.platform) {
+            platform.source()
+        } else {
+            warn!(
+                "unknown platform `{}` for timer `{}`",
+                target.platform, name
+            );
+            return None;
+        };
+
+        Some(Self {
+            target: target.clone(),
+            source,
+            last_post: now,
+            last_lines: activity.lines(&target.platform),
+        })
+    }
+}
+
+impl Timers {
+    /// Set up all timers from the config. The first post of each timer happens one full interval
+    /// after the given start time.
+    #[must_use]
+    pub fn new(config: &Config, activity: &Activity, now: DateTime<Utc>) -> Self {
+        let entries = config
+            .timers
+            .iter()
+            .map(|(name, timer)| Entry {
+                name: name.clone(),
+                message: timer.message.clone(),
+                interval: Duration::minutes(u32::from(timer.interval).into()),
+                min_lines: timer.min_lines.into(),
+                targets: timer
+                    .targets
+                    .iter()
+                    .filter_map(|target| TargetState::new(config, name, target, activity, now))
+                    .collect(),
+            })
+            .collect();
+
+        Self { entries }
+    }
+
+    /// Collect all messages that are due at the given time, together with the name of the
+    /// platform they should be posted to. Paused timers are skipped.
+    pub fn poll(
+        &mut self,
+        state: &State,
+        activity: &Activity,
+        now: DateTime<Utc>,
+    ) -> Vec<(String, Outgoing)> {
+        let mut due = Vec::new();
+
+        for entry in &mut self.entries {
+            if state.paused_timers.contains(&entry.name) {
+                continue;
+            }
+
+            for target in &mut entry.targets {
+                let lines = activity.lines(&target.target.platform);
+                if now - target.last_post < entry.interval
+                    || lines - target.last_lines < entry.min_lines
+                {
+                    continue;
+                }
+
+                // Outgoing messages are plain text, so embeds are flattened everywhere.
+                let content = match entry.message.get(target.source).map(commands::render_plain) {
+                    Some(Ok(content)) => content,
+                    Some(Err(e)) => {
+                        warn!("failed rendering timer `{}`: {}", entry.name, e);
+                        continue;
+                    }
+                    None => continue,
+                };
+
+                target.last_post = now;
+                target.last_lines = lines;
+                due.push((
+                    target.target.platform.clone(),
+                    Outgoing {
+                        channel: target.target.channel,
+                        content,
+                    },
+                ));
+            }
+        }
+
+        due
+    }
+}
+
+/// Periodically post all due timer messages until a shutdown is requested.
+pub async fn run(
+    mut timers: Timers,
+    state: AsyncState,
+    activity: Arc<Activity>,
+    outboxes: Outboxes,
+    clock: impl Clock,
+    mut shutdown: Shutdown,
+) {
+    let mut interval = time::interval(POLL_INTERVAL);
+
+    loop {
+        select! {
+            _ = shutdown.recv() => break,
+            _ = interval.tick() => {}
+        }
+
+        let due = timers.poll(&*state.read().await, &activity, clock.now());
+        for (platform, message) in due {
+            if let Some(outbox) = outboxes.get(&platform) {
+                if outbox.send(message).await.is_err() {
+                    warn!("platform `{}` is not running anymore", platform);
+                }
+            }
+        }
+    }
+
+    info!("timers shutting down");
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::settings::{Discord, Platform, Timer};
+
+    fn config() -> Config {
+        let mut config = Config::default();
+        config.platforms.insert(
+            "discord".to_owned(),
+            Platform::Discord(Discord {
+                token: String::new(),
+                guild: None,
+            }),
+        );
+        config.timers.insert(
+            "discord".to_owned(),
+            Timer {
+                message: FormatString::Universal("Join the Discord!".to_owned()),
+                interval: std::num::NonZeroU32::new(20).unwrap(),
+                min_lines: 2,
+                targets: vec![Target {
+                    platform: "discord".to_owned(),
+                    channel: Some(1),
+                }],
+            },
+        );
+        config
+    }
+
+    fn posted(due: &[(String, Outgoing)]) -> Vec<&str> {
+        due.iter()
+            .map(|(_, message)| message.content.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn post_after_interval_and_lines() {
+        let start = Utc.ymd(2026, 12, 21).and_hms(12, 0, 0);
+        let mut state = State::default();
+        let config = config();
+        let activity = Activity::new(&config);
+        let mut timers = Timers::new(&config, &activity, start);
+
+        activity.record("discord");
+        activity.record("discord");
+        assert!(timers
+            .poll(&state, &activity, start + Duration::minutes(10))
+            .is_empty());
+        assert_eq!(
+            vec!["Join the Discord!"],
+            posted(&timers.poll(&state, &activity, start + Duration::minutes(20)))
+        );
+
+        // Enough time passed, but the chat was too quiet.
+        activity.record("discord");
+        assert!(timers
+            .poll(&state, &activity, start + Duration::minutes(45))
+            .is_empty());
+
+        activity.record("discord");
+        state.paused_timers.insert("discord".to_owned());
+        assert!(timers
+            .poll(&state, &activity, start + Duration::minutes(50))
+            .is_empty());
+
+        state.paused_timers.clear();
+        assert_eq!(
+            vec!["Join the Discord!"],
+            posted(&timers.poll(&state, &activity, start + Duration::minutes(50)))
+        );
+    }
+
+    #[test]
+    fn render_templates() {
+        let start = Utc.ymd(2026, 12, 21).and_hms(12, 0, 0);
+        let mut config = config();
+        config.timers.get_mut("discord").unwrap().message =
+            FormatString::Universal(r#"Join the {upper: 1 | "discord"} {{server}}!"#.to_owned());
+        let activity = Activity::new(&config);
+        let mut timers = Timers::new(&config, &activity, start);
+
+        activity.record("discord");
+        activity.record("discord");
+        // Messages on other platforms don't count.
+        activity.record("twitch");
+        assert_eq!(
+            vec!["Join the DISCORD {server}!"],
+            posted(&timers.poll(&State::default(), &activity, start + Duration::minutes(20)))
+        );
+    }
+}
@@ -0,0 +1,129 @@
+//! Moderation through the Twitch API, as Twitch no longer supports chat commands like `/delete`
+//! and `/timeout`.
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use log::info;
+use reqwest::{
+    header::{AUTHORIZATION, CONTENT_TYPE},
+    Client, RequestBuilder,
+};
+use serde::Deserialize;
+use serde_json::json;
+
+/// Endpoint that tells the account and application that a token belongs to.
+const VALIDATE_URL: &str = "https://id.twitch.tv/oauth2/validate";
+const API_URL: &str = "https://api.twitch.tv/helix";
+/// Longest time in seconds that Twitch allows to time out a user for.
+const MAX_TIMEOUT: u32 = 14 * 24 * 60 * 60;
+/// Time to wait for a whole request, including the connection.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Client for the moderation endpoints, acting as the bot account. The token needs the
+/// `moderator:manage:chat_messages` and `moderator:manage:banned_users` scopes.
+pub struct Helix {
+    client: Client,
+    token: String,
+    client_id: String,
+    /// User ID of the bot account, that takes the moderation actions.
+    moderator_id: String,
+}
+
+#[derive(Deserialize)]
+struct Validation {
+    client_id: String,
+    login: String,
+    user_id: String,
+}
+
+impl Helix {
+    /// Look up the application and account that the token belongs to, which every request needs
+    /// to name.
+    pub async fn new(token: &str) -> Result<Self> {
+        let client = Client::builder()
+            .user_agent("ToggleBot")
+            .timeout(REQUEST_TIMEOUT)
+            .build()?;
+        let token = token.trim_start_matches("oauth:").to_owned();
+
+        let body = client
+            .get(VALIDATE_URL)
+            .header(AUTHORIZATION, format!("OAuth {}", token))
+            .send()
+            .await?
+            .error_for_status()
+            .context("failed validating the Twitch token")?
+            .bytes()
+            .await?;
+        let validation = serde_json::from_slice::<Validation>(&body)?;
+        info!("twitch moderation acting as {}", validation.login);
+
+        Ok(Self {
+            client,
+            token,
+            client_id: validation.client_id,
+            moderator_id: validation.user_id,
+        })
+    }
+
+    /// Delete a single chat message in the broadcaster's channel.
+    pub async fn delete_message(&self, broadcaster_id: &str, message_id: &str) -> Result<()> {
+        let request = self
+            .client
+            .delete(format!("{}/moderation/chat", API_URL))
+            .query(&[
+                ("broadcaster_id", broadcaster_id),
+                ("moderator_id", &self.moderator_id),
+                ("message_id", message_id),
+            ]);
+
+        self.send(request)
+            .await
+            .context("failed deleting Twitch message")
+    }
+
+    /// Keep the user from chatting in the broadcaster's channel for the given seconds. Longer
+    /// timeouts than Twitch allows are shortened to the maximum.
+    pub async fn timeout(
+        &self,
+        broadcaster_id: &str,
+        user_id: &str,
+        seconds: u32,
+        reason: &str,
+    ) -> Result<()> {
+        let request = self
+            .client
+            .post(format!("{}/moderation/bans", API_URL))
+            .query(&[
+                ("broadcaster_id", broadcaster_id),
+                ("moderator_id", &self.moderator_id),
+            ])
+            .header(CONTENT_TYPE, "application/json")
+            .body(
+                json!({
+                    "data": {
+                        "user_id": user_id,
+                        "duration": seconds.min(MAX_TIMEOUT),
+                        "reason": reason,
+                    }
+                })
+                .to_string(),
+            );
+
+        self.send(request)
+            .await
+            .context("failed timing out Twitch user")
+    }
+
+    async fn send(&self, request: RequestBuilder) -> Result<()> {
+        request
+            .bearer_auth(&self.token)
+            .header("Client-Id", &self.client_id)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,509 @@
+//! Twitch service connector that allows to receive commands from Twitch channels.
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use log::{error, info, warn};
+use tokio::{select, sync::oneshot};
+use twitch_irc::{
+    login::StaticLoginCredentials,
+    message::{PrivmsgMessage, ServerMessage},
+    ClientConfig, TCPTransport, TwitchIRCClient,
+};
+
+use crate::{
+    commands::{crates::CrateLookup, playground::Evaluation},
+    emojis,
+    moderation::Violation,
+    render::{self, TWITCH_LIMIT},
+    settings::{Links, ModerationAction, Quote, Twitch},
+    AdminResponse, ArgsError, Message, Outbox, Queue, Response, Shutdown, Source, UserResponse,
+};
+
+use self::{helix::Helix, sender::Sender};
+
+mod helix;
+mod sender;
+
+type Client = TwitchIRCClient<TCPTransport, StaticLoginCredentials>;
+
+/// Maximum number of messages that a long response is split into. Anything longer is cut off, to
+/// not flood the chat.
+const MAX_PARTS: usize = 3;
+
+#[allow(clippy::missing_panics_doc)]
+pub async fn start(
+    platform: String,
+    config: &Twitch,
+    moderation: bool,
+    queue: Queue,
+    mut outbox: Outbox,
+    mut shutdown: Shutdown,
+) -> Result<()> {
+    let irc_config = ClientConfig::new_simple(StaticLoginCredentials::new(
+        config.login.clone(),
+        Some(config.token.clone()),
+    ));
+    let (mut messages, client) = Client::new(irc_config);
+    let channel = config.channel.clone();
+
+    client.join(channel.clone());
+    let sender = Sender::new(client);
+    // The API is only needed for moderation, and chat keeps working without it.
+    let helix = if moderation {
+        match Helix::new(&config.token).await {
+            Ok(helix) => Some(Arc::new(helix)),
+            Err(e) => {
+                error!("twitch moderation unavailable: {:?}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    tokio::spawn(async move {
+        loop {
+            select! {
+                _ = shutdown.recv() => break,
+                message = messages.recv() => {
+                    if let Some(message) = message {
+                        let sender = sender.clone();
+                        let queue = queue.clone();
+                        let channel = channel.clone();
+                        let helix = helix.clone();
+                        let platform = platform.clone();
+                        tokio::spawn(async move {
+                            if let Err(e) = handle_server_message(queue, message, sender, helix, channel, platform).await {
+                                error!("error during event handling: {}", e);
+                            }
+                        });
+                    } else {
+                        break;
+                    }
+                }
+                Some(message) = outbox.recv() => {
+                    if let Err(e) = sender.say(channel.clone(), message.content).await {
+                        error!("error during outgoing message: {}", e);
+                    }
+                }
+            }
+        }
+
+        if let Some(metrics) = sender.metrics(&channel) {
+            info!("twitch outgoing messages: {:?}", metrics);
+        }
+        info!("twitch connection shutting down");
+    });
+
+    Ok(())
+}
+
+async fn handle_server_message(
+    queue: Queue,
+    message: ServerMessage,
+    sender: Sender,
+    helix: Option<Arc<Helix>>,
+    channel: String,
+    platform: String,
+) -> Result<()> {
+    match message {
+        ServerMessage::Privmsg(msg) => {
+            handle_message(queue, msg, sender, helix, channel, platform).await?;
+        }
+        ServerMessage::UserState(state) => sender.set_moderator(
+            &state.channel_login,
+            state
+                .badges
+                .iter()
+                .any(|badge| badge.name == "moderator" || badge.name == "broadcaster"),
+        ),
+        ServerMessage::Join(_) => info!("twitch connection ready, listening for events"),
+        _ => {}
+    }
+
+    Ok(())
+}
+
+async fn handle_message(
+    queue: Queue,
+    msg: PrivmsgMessage,
+    sender: Sender,
+    helix: Option<Arc<Helix>>,
+    channel: String,
+    platform: String,
+) -> Result<()> {
+    let message = Message {
+        source: Source::Twitch,
+        platform,
+        content: msg.message_text.clone(),
+        admin: false,
+        moderator: msg
+            .badges
+            .iter()
+            .any(|badge| badge.name == "moderator" || badge.name == "broadcaster"),
+        author: msg.sender.name.clone(),
+    };
+    let (tx, rx) = oneshot::channel();
+
+    if queue.send((message, tx)).await.is_ok() {
+        if let Ok(resp) = rx.await {
+            match resp {
+                Response::User(user_resp) => {
+                    handle_user_message(user_resp, msg, sender, channel).await?
+                }
+                Response::Admin(admin_resp) => {
+                    handle_admin_message(admin_resp, msg, sender, channel).await?
+                }
+                Response::Moderation(violation) => {
+                    handle_moderation(violation, msg, sender, helix.as_deref(), channel).await
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[allow(clippy::match_same_arms)]
+async fn handle_user_message(
+    resp: UserResponse,
+    msg: PrivmsgMessage,
+    sender: Sender,
+    channel: String,
+) -> Result<()> {
+    match resp {
+        UserResponse::Commands { names, page } => {
+            handle_commands(msg, sender, channel, names, page).await
+        }
+        UserResponse::Links(links) => handle_links(msg, sender, channel, links).await,
+        UserResponse::Schedule {
+            start,
+            finish,
+            off_days,
+            exceptions,
+        } => handle_schedule(msg, sender, channel, start, finish, off_days, exceptions).await,
+        UserResponse::Custom(content) => handle_custom(msg, sender, channel, content).await,
+        UserResponse::Embed(embed) => {
+            handle_custom(msg, sender, channel, render::flatten(&embed)).await
+        }
+        UserResponse::Quote(quote) => handle_quote(msg, sender, channel, quote).await,
+        UserResponse::Crate(lookup) => handle_crate(msg, sender, channel, lookup).await,
+        UserResponse::Play(res) => handle_play(msg, sender, channel, res).await,
+        UserResponse::Unknown => Ok(()),
+        UserResponse::WrongArgs(error) => handle_wrong_args(msg, sender, channel, error).await,
+    }
+}
+
+async fn handle_admin_message(
+    resp: AdminResponse,
+    msg: PrivmsgMessage,
+    sender: Sender,
+    channel: String,
+) -> Result<()> {
+    let message = match resp {
+        AdminResponse::CustomCommands(Ok(_)) => {
+            format!("{} custom commands updated", emojis::OK_HAND)
+        }
+        AdminResponse::CustomCommands(Err(e)) => format!("{} {}", emojis::COLLISION, e),
+        _ => return Ok(()),
+    };
+
+    sender.reply(channel, msg.message_id, message).await?;
+
+    Ok(())
+}
+
+/// Act on a message that broke a moderation rule, through the Twitch API. Every action is taken
+/// on its own, so a failed deletion still lets the warning through.
+async fn handle_moderation(
+    violation: Violation,
+    msg: PrivmsgMessage,
+    sender: Sender,
+    helix: Option<&Helix>,
+    channel: String,
+) {
+    let wants_api =
+        violation.has(ModerationAction::Delete) || violation.has(ModerationAction::Timeout);
+
+    match helix {
+        Some(helix) => {
+            if violation.has(ModerationAction::Delete) {
+                if let Err(e) = helix.delete_message(&msg.channel_id, &msg.message_id).await {
+                    error!("twitch moderation failed: {:?}", e);
+                }
+            }
+
+            if violation.has(ModerationAction::Timeout) {
+                if let Err(e) = helix
+                    .timeout(
+                        &msg.channel_id,
+                        &msg.sender.id,
+                        violation.timeout,
+                        violation.reason,
+                    )
+                    .await
+                {
+                    error!("twitch moderation failed: {:?}", e);
+                }
+            }
+        }
+        None if wants_api => warn!("can't moderate twitch message without the API"),
+        None => {}
+    }
+
+    if violation.has(ModerationAction::Warn) {
+        let user = format!("@{}", msg.sender.name);
+        if let Err(e) = sender.say(channel, violation.warning(&user)).await {
+            error!("failed warning twitch user: {}", e);
+        }
+    }
+}
+
+async fn handle_commands(
+    msg: PrivmsgMessage,
+    sender: Sender,
+    channel: String,
+    res: Result<Vec<String>>,
+    page: usize,
+) -> Result<()> {
+    let message = match res {
+        Ok(names) => {
+            let names = names
+                .iter()
+                .map(|name| format!("!{}", name))
+                .collect::<Vec<_>>();
+            // Leave some room for the introduction and the page number.
+            let limit = TWITCH_LIMIT - 80;
+
+            match render::page(&names, ", ", limit, page) {
+                (Some(list), 1) => format!("Available commands: {}", list),
+                (Some(list), total) => format!(
+                    "Available commands (page {} of {}, see more with !commands <page>): {}",
+                    page, total, list
+                ),
+                (None, total) => format!("The last page of commands is {}", total),
+            }
+        }
+        Err(e) => {
+            error!("failed listing commands: {}", e);
+            "Sorry, something went wrong fetching the list of commands".to_owned()
+        }
+    };
+
+    sender.reply(channel, msg.message_id, message).await?;
+
+    Ok(())
+}
+
+async fn handle_links(
+    msg: PrivmsgMessage,
+    sender: Sender,
+    channel: String,
+    links: Links,
+) -> Result<()> {
+    sender
+        .reply(
+            channel,
+            msg.message_id,
+            links
+                .into_iter()
+                .enumerate()
+                .fold(String::new(), |mut list, (i, (name, url))| {
+                    if i > 0 {
+                        list.push_str(" | ");
+                    }
+
+                    list.push_str(&name);
+                    list.push_str(": ");
+                    list.push_str(&url);
+                    list
+                }),
+        )
+        .await?;
+
+    Ok(())
+}
+
+async fn handle_schedule(
+    msg: PrivmsgMessage,
+    sender: Sender,
+    channel: String,
+    start: String,
+    finish: String,
+    off_days: Vec<String>,
+    exceptions: Vec<String>,
+) -> Result<()> {
+    let last_off_day = off_days.len() - 1;
+    let days = format!(
+        "Every day, except {}",
+        off_days
+            .into_iter()
+            .enumerate()
+            .fold(String::new(), |mut days, (i, day)| {
+                if i == last_off_day {
+                    days.push_str(" and ");
+                } else if i > 0 {
+                    days.push_str(", ");
+                }
+
+                days.push_str(&day);
+                days
+            })
+    );
+    let time = format!("Starting around {}, finishing around {}", start, finish);
+    let mut message = format!("{} | {} | Timezone CET", days, time);
+
+    if !exceptions.is_empty() {
+        message.push_str(" | Exceptions: ");
+        message.push_str(&exceptions.join(", "));
+    }
+
+    sender.reply(channel, msg.message_id, message).await?;
+    info!("Replied");
+
+    Ok(())
+}
+
+async fn handle_custom(
+    msg: PrivmsgMessage,
+    sender: Sender,
+    channel: String,
+    content: String,
+) -> Result<()> {
+    reply_long(&msg, &sender, channel, &content).await
+}
+
+async fn handle_wrong_args(
+    msg: PrivmsgMessage,
+    sender: Sender,
+    channel: String,
+    error: ArgsError,
+) -> Result<()> {
+    let message = match error.message {
+        Some(message) => message,
+        None => format!(
+            "{} {}, usage: {}",
+            emojis::COLLISION,
+            error.reason,
+            error.usage
+        ),
+    };
+
+    sender.reply(channel, msg.message_id, message).await?;
+
+    Ok(())
+}
+
+async fn handle_quote(
+    msg: PrivmsgMessage,
+    sender: Sender,
+    channel: String,
+    quote: Option<Quote>,
+) -> Result<()> {
+    let message = match quote {
+        Some(quote) => format!(
+            "#{}: \"{}\" (added by {} on {} via {})",
+            quote.number,
+            quote.text,
+            quote.author,
+            quote.date,
+            quote.source.as_ref()
+        ),
+        None => "No matching quote found".to_owned(),
+    };
+
+    sender.reply(channel, msg.message_id, message).await?;
+
+    Ok(())
+}
+
+async fn handle_crate(
+    msg: PrivmsgMessage,
+    sender: Sender,
+    channel: String,
+    lookup: Result<CrateLookup>,
+) -> Result<()> {
+    let message = match lookup {
+        Ok(CrateLookup::Found(info)) => match info.description.filter(|d| !d.is_empty()) {
+            Some(description) => format!(
+                "{} {} ({} downloads): {} - {}",
+                info.name, info.version, info.downloads, description, info.docs
+            ),
+            None => format!(
+                "{} {} ({} downloads) - {}",
+                info.name, info.version, info.downloads, info.docs
+            ),
+        },
+        Ok(CrateLookup::NotFound { name, suggestions }) if suggestions.is_empty() => {
+            format!("No crate named {} found", name)
+        }
+        Ok(CrateLookup::NotFound { name, suggestions }) => format!(
+            "No crate named {} found, did you mean {}?",
+            name,
+            suggestions.join(", ")
+        ),
+        Err(e) => {
+            error!("failed looking up crate: {}", e);
+            "Sorry, something went wrong looking up the crate".to_owned()
+        }
+    };
+
+    sender.reply(channel, msg.message_id, message).await?;
+
+    Ok(())
+}
+
+async fn handle_play(
+    msg: PrivmsgMessage,
+    sender: Sender,
+    channel: String,
+    res: Result<Evaluation>,
+) -> Result<()> {
+    let message = match res {
+        Ok(eval) => {
+            let output = if eval.output.is_empty() {
+                "no output".to_owned()
+            } else {
+                eval.output.lines().collect::<Vec<_>>().join(" ⏎ ")
+            };
+
+            if eval.success {
+                output
+            } else {
+                format!("{} failed: {}", emojis::COLLISION, output)
+            }
+        }
+        Err(e) => {
+            error!("failed running code: {}", e);
+            "Sorry, something went wrong running the code".to_owned()
+        }
+    };
+
+    reply_long(&msg, &sender, channel, &message).await
+}
+
+/// Reply with content that might be longer than a single message, by splitting it into several
+/// ones. Anything beyond [`MAX_PARTS`] messages is cut off.
+async fn reply_long(
+    msg: &PrivmsgMessage,
+    sender: &Sender,
+    channel: String,
+    content: &str,
+) -> Result<()> {
+    let mut parts = render::split(content, TWITCH_LIMIT);
+    if parts.len() > MAX_PARTS {
+        parts.truncate(MAX_PARTS);
+        if let Some(last) = parts.last_mut() {
+            *last = render::truncate(&format!("{} …", last), TWITCH_LIMIT);
+        }
+    }
+
+    for part in parts {
+        sender
+            .reply(channel.clone(), msg.message_id.clone(), part)
+            .await?;
+    }
+
+    Ok(())
+}
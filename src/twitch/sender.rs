@@ -0,0 +1,402 @@
+//! Outgoing chat messages, which are sent no faster than Twitch allows.
+//!
+//! Twitch allows 20 messages every 30 seconds, or 100 if the bot is a moderator or the
+//! broadcaster of the channel. Messages beyond that are dropped and the account may even be banned
+//! from chat for a while. Each channel therefore gets its own queue, which is worked off by a
+//! token bucket that knows about the bot's status in that channel.
+
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use log::{error, info, warn};
+use tokio::sync::mpsc;
+
+use super::Client;
+
+/// Time window that Twitch's rate limits apply to.
+const WINDOW: Duration = Duration::from_secs(30);
+/// Messages per window for regular users.
+const USER_LIMIT: u32 = 20;
+/// Messages per window for moderators and the broadcaster.
+const MODERATOR_LIMIT: u32 = 100;
+/// Time in which Twitch rejects a message that is identical to the previous one.
+const DUPLICATE_WINDOW: Duration = Duration::from_secs(30);
+/// Maximum number of waiting messages per channel. Replies wait for free space beyond that.
+const QUEUE_CAPACITY: usize = 50;
+/// Queue depth at which the queue is considered congested and a warning is logged.
+const WARN_DEPTH: usize = 10;
+
+/// Handle to the outgoing queues of all channels, which can be cloned cheaply.
+#[derive(Clone)]
+pub struct Sender {
+    client: Client,
+    channels: Arc<Mutex<HashMap<String, Channel>>>,
+}
+
+#[derive(Clone)]
+struct Channel {
+    tx: mpsc::Sender<Line>,
+    moderator: Arc<AtomicBool>,
+    metrics: Arc<Metrics>,
+}
+
+struct Line {
+    content: String,
+    reply_to: Option<String>,
+}
+
+impl Sender {
+    #[must_use]
+    pub fn new(client: Client) -> Self {
+        Self {
+            client,
+            channels: Arc::default(),
+        }
+    }
+
+    /// Queue a message for the channel.
+    pub async fn say(&self, channel: String, content: String) -> Result<()> {
+        self.send(
+            channel,
+            Line {
+                content,
+                reply_to: None,
+            },
+        )
+        .await
+    }
+
+    /// Queue a reply to the message with the given ID.
+    pub async fn reply(&self, channel: String, message_id: String, content: String) -> Result<()> {
+        self.send(
+            channel,
+            Line {
+                content,
+                reply_to: Some(message_id),
+            },
+        )
+        .await
+    }
+
+    /// Update whether the bot is a moderator in the channel, which raises its rate limit.
+    pub fn set_moderator(&self, channel: &str, moderator: bool) {
+        let previous = self
+            .channel(channel)
+            .moderator
+            .swap(moderator, Ordering::Relaxed);
+        if previous != moderator {
+            info!(
+                "bot is {} in {}, adjusting the rate limit",
+                if moderator {
+                    "a moderator"
+                } else {
+                    "a regular user"
+                },
+                channel
+            );
+        }
+    }
+
+    /// Current numbers about the outgoing messages of the channel, or [`None`] if nothing was
+    /// sent to it yet.
+    #[must_use]
+    pub fn metrics(&self, channel: &str) -> Option<MetricsSnapshot> {
+        self.channels
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(channel)
+            .map(|channel| channel.metrics.snapshot())
+    }
+
+    async fn send(&self, channel: String, line: Line) -> Result<()> {
+        let queue = self.channel(&channel);
+
+        let depth = queue.metrics.enqueue();
+        if depth >= WARN_DEPTH {
+            warn!("{} messages waiting to be sent to {}", depth, channel);
+        }
+
+        queue
+            .tx
+            .send(line)
+            .await
+            .map_err(|_| anyhow!("outgoing queue for {} is closed", channel))
+    }
+
+    /// Get the queue of the channel, starting a new one on first use.
+    fn channel(&self, channel: &str) -> Channel {
+        self.channels
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(channel.to_owned())
+            .or_insert_with(|| {
+                let (tx, rx) = mpsc::channel(QUEUE_CAPACITY);
+                let queue = Channel {
+                    tx,
+                    moderator: Arc::default(),
+                    metrics: Arc::default(),
+                };
+
+                tokio::spawn(work(
+                    self.client.clone(),
+                    channel.to_owned(),
+                    rx,
+                    queue.moderator.clone(),
+                    queue.metrics.clone(),
+                ));
+
+                queue
+            })
+            .clone()
+    }
+}
+
+/// Send out the queued messages of a single channel, as fast as the rate limit allows.
+async fn work(
+    client: Client,
+    channel: String,
+    mut rx: mpsc::Receiver<Line>,
+    moderator: Arc<AtomicBool>,
+    metrics: Arc<Metrics>,
+) {
+    let mut limiter = Limiter::new(Instant::now());
+
+    while let Some(line) = rx.recv().await {
+        metrics.received.fetch_add(1, Ordering::Relaxed);
+
+        if limiter.is_duplicate(&line.content, Instant::now()) {
+            info!("suppressed duplicate message to {}", channel);
+            metrics.duplicates.fetch_add(1, Ordering::Relaxed);
+            continue;
+        }
+
+        loop {
+            limiter.set_moderator(moderator.load(Ordering::Relaxed));
+            match limiter.take(Instant::now()) {
+                Ok(()) => break,
+                Err(wait) => tokio::time::sleep(wait).await,
+            }
+        }
+
+        let res = match line.reply_to {
+            Some(id) => {
+                client
+                    .say_in_response(channel.clone(), line.content.clone(), Some(id))
+                    .await
+            }
+            None => client.say(channel.clone(), line.content.clone()).await,
+        };
+
+        match res {
+            Ok(()) => {
+                limiter.sent(line.content, Instant::now());
+                metrics.sent.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(e) => error!("failed sending message to {}: {}", channel, e),
+        }
+    }
+
+    info!("outgoing queue for {} closed", channel);
+}
+
+/// Counters about the outgoing messages of a channel.
+#[derive(Default)]
+struct Metrics {
+    /// Messages that were put into the queue.
+    queued: AtomicU64,
+    /// Messages that were taken out of the queue.
+    received: AtomicU64,
+    sent: AtomicU64,
+    duplicates: AtomicU64,
+    /// Highest queue depth so far.
+    max_depth: AtomicUsize,
+}
+
+/// Point in time view of the outgoing messages of a channel.
+#[derive(Debug, PartialEq, Eq)]
+pub struct MetricsSnapshot {
+    /// Messages that are currently waiting to be sent.
+    pub depth: usize,
+    /// Highest number of messages that were waiting at the same time.
+    pub max_depth: usize,
+    pub sent: u64,
+    /// Messages that were dropped because they were the same as the previous one.
+    pub duplicates: u64,
+}
+
+impl Metrics {
+    /// Record a newly queued message and return the queue depth before it.
+    fn enqueue(&self) -> usize {
+        let depth = self.depth();
+        self.queued.fetch_add(1, Ordering::Relaxed);
+        self.max_depth.fetch_max(depth + 1, Ordering::Relaxed);
+        depth
+    }
+
+    fn depth(&self) -> usize {
+        let queued = self.queued.load(Ordering::Relaxed);
+        let received = self.received.load(Ordering::Relaxed);
+        usize::try_from(queued.saturating_sub(received)).unwrap_or(usize::MAX)
+    }
+
+    fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            depth: self.depth(),
+            max_depth: self.max_depth.load(Ordering::Relaxed),
+            sent: self.sent.load(Ordering::Relaxed),
+            duplicates: self.duplicates.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Token bucket for the messages of a single channel, together with the last sent message to
+/// detect duplicates.
+///
+/// The burst size and refill rate are chosen so that no window of 30 seconds ever contains more
+/// messages than Twitch allows, even right after a full burst.
+struct Limiter {
+    moderator: bool,
+    tokens: f64,
+    updated: Instant,
+    last: Option<(String, Instant)>,
+}
+
+impl Limiter {
+    fn new(now: Instant) -> Self {
+        Self {
+            moderator: false,
+            tokens: Self::burst(false),
+            updated: now,
+            last: None,
+        }
+    }
+
+    /// Messages that can be sent at once, which is a quarter of the limit.
+    fn burst(moderator: bool) -> f64 {
+        f64::from(Self::limit(moderator) / 4)
+    }
+
+    /// Tokens that are added per second, so that burst and refill together stay in the limit.
+    fn rate(moderator: bool) -> f64 {
+        (f64::from(Self::limit(moderator)) - Self::burst(moderator)) / WINDOW.as_secs_f64()
+    }
+
+    const fn limit(moderator: bool) -> u32 {
+        if moderator {
+            MODERATOR_LIMIT
+        } else {
+            USER_LIMIT
+        }
+    }
+
+    fn set_moderator(&mut self, moderator: bool) {
+        self.moderator = moderator;
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens =
+            (self.tokens + elapsed * Self::rate(self.moderator)).min(Self::burst(self.moderator));
+        self.updated = now;
+    }
+
+    /// Take a token for a message, or get the time to wait until the next one is available.
+    fn take(&mut self, now: Instant) -> Result<(), Duration> {
+        self.refill(now);
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            let missing = 1.0 - self.tokens;
+            Err(Duration::from_secs_f64(
+                missing / Self::rate(self.moderator),
+            ))
+        }
+    }
+
+    fn is_duplicate(&self, content: &str, now: Instant) -> bool {
+        match &self.last {
+            Some((last, sent)) => last == content && *sent + DUPLICATE_WINDOW > now,
+            None => false,
+        }
+    }
+
+    fn sent(&mut self, content: String, now: Instant) {
+        self.last = Some((content, now));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn limiter_rates() {
+        let start = Instant::now();
+        let mut limiter = Limiter::new(start);
+
+        for _ in 0..5 {
+            assert_eq!(Ok(()), limiter.take(start));
+        }
+        assert_eq!(Err(Duration::from_secs(2)), limiter.take(start));
+        assert_eq!(Ok(()), limiter.take(start + Duration::from_secs(2)));
+
+        // The burst is only refilled slowly, so a full window stays within the limit.
+        let mut sent = 6;
+        let mut now = start + Duration::from_secs(2);
+        while now < start + WINDOW {
+            if limiter.take(now).is_ok() {
+                sent += 1;
+            }
+            now += Duration::from_millis(100);
+        }
+        assert!(sent <= USER_LIMIT, "sent {} messages", sent);
+
+        // Moderators refill faster.
+        limiter.set_moderator(true);
+        assert_eq!(Ok(()), limiter.take(now + Duration::from_secs(1)));
+        assert_eq!(Ok(()), limiter.take(now + Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn duplicates() {
+        let start = Instant::now();
+        let mut limiter = Limiter::new(start);
+
+        assert!(!limiter.is_duplicate("hello", start));
+        limiter.sent("hello".to_owned(), start);
+        assert!(limiter.is_duplicate("hello", start + Duration::from_secs(10)));
+        assert!(!limiter.is_duplicate("hello!", start + Duration::from_secs(10)));
+        assert!(!limiter.is_duplicate("hello", start + DUPLICATE_WINDOW));
+    }
+
+    #[test]
+    fn queue_depth() {
+        let metrics = Metrics::default();
+        assert_eq!(0, metrics.enqueue());
+        assert_eq!(1, metrics.enqueue());
+        metrics.received.fetch_add(1, Ordering::Relaxed);
+        metrics.sent.fetch_add(1, Ordering::Relaxed);
+
+        assert_eq!(
+            MetricsSnapshot {
+                depth: 1,
+                max_depth: 2,
+                sent: 1,
+                duplicates: 0,
+            },
+            metrics.snapshot()
+        );
+    }
+}